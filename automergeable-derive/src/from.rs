@@ -2,43 +2,83 @@ use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 use syn::{
     punctuated::Punctuated, token::Comma, Attribute, Data, DataEnum, DataStruct, DeriveInput,
-    Fields, Ident, Lit, Meta, NestedMeta, Type, Variant,
+    Fields, GenericParam, Ident, Lifetime, LifetimeDef, Lit, Meta, NestedMeta, Type, Variant,
 };
 
 use crate::utils;
 
 pub fn from_automerge(input: &DeriveInput) -> TokenStream {
-    match &input.data {
-        Data::Struct(DataStruct { fields, .. }) => from_automerge_struct(input, fields),
-        Data::Enum(DataEnum { variants, .. }) => from_automerge_enum(input, variants),
-        Data::Union(_) => panic!("this derive macro only works on structs"),
-    }
-}
-
-fn from_automerge_struct(input: &DeriveInput, fields: &Fields) -> TokenStream {
     let crate_path = utils::crate_path(input);
     let t_name = &input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
-    let fields_from_automerge = fields_from_automerge(fields, None, &crate_path);
+    let body = from_automerge_body(input, &crate_path, None);
     quote! {
         #[automatically_derived]
         impl #impl_generics #crate_path::FromAutomerge for #t_name #ty_generics #where_clause {
             fn from_automerge(value: &automerge::Value) -> ::std::result::Result<Self, #crate_path::FromAutomergeError> {
-                #fields_from_automerge
+                #body
             }
         }
     }
 }
 
-fn from_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma>) -> TokenStream {
+pub fn from_automerge_ref(input: &DeriveInput) -> TokenStream {
     let crate_path = utils::crate_path(input);
     let t_name = &input.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // borrow from the value for the type's first lifetime, or for any lifetime if it has none
+    let mut generics = input.generics.clone();
+    let lifetime = if let Some(l) = input.generics.lifetimes().next() {
+        l.lifetime.clone()
+    } else {
+        let l = Lifetime::new("'automerge", proc_macro2::Span::call_site());
+        generics
+            .params
+            .insert(0, GenericParam::Lifetime(LifetimeDef::new(l.clone())));
+        l
+    };
+    let (impl_generics, _, _) = generics.split_for_impl();
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = from_automerge_body(input, &crate_path, Some(&lifetime));
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::FromAutomergeRef<#lifetime> for #t_name #ty_generics #where_clause {
+            fn from_automerge_ref(value: &#lifetime automerge::Value) -> ::std::result::Result<Self, #crate_path::FromAutomergeError> {
+                #body
+            }
+        }
+    }
+}
+
+/// Build the body of the conversion, borrowing from the value for `lifetime` if given.
+fn from_automerge_body(
+    input: &DeriveInput,
+    crate_path: &TokenStream,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream {
+    match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            fields_from_automerge(fields, None, crate_path, lifetime)
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            from_automerge_enum(input, variants, crate_path, lifetime)
+        }
+        Data::Union(_) => panic!("this derive macro only works on structs"),
+    }
+}
+
+fn from_automerge_enum(
+    input: &DeriveInput,
+    variants: &Punctuated<Variant, Comma>,
+    crate_path: &TokenStream,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream {
+    let t_name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
     let variant_match = variants.iter().filter(|v| !v.fields.is_empty()).map(|v| {
         let v_name = &v.ident;
         let v_name_string = v_name.to_string();
         let fields_from_automerge =
-            fields_from_automerge(&v.fields, Some(v_name.clone()), &crate_path);
+            fields_from_automerge(&v.fields, Some(v_name.clone()), crate_path, lifetime);
         quote! {
             (#v_name_string, value) => {#fields_from_automerge}
         }
@@ -52,49 +92,40 @@ fn from_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma
     });
 
     quote! {
-        #[automatically_derived]
-        impl #impl_generics #crate_path::FromAutomerge for #t_name #ty_generics #where_clause {
-            fn from_automerge(value: &automerge::Value) -> ::std::result::Result<Self, #crate_path::FromAutomergeError> {
-                if let automerge::Value::Map(hm) = value {
-                    if hm.len() != 1 {
-                        Err(#crate_path::FromAutomergeError::WrongType {
-                            found: value.clone(),
-                            expected: "a map with one item".to_owned(),
-                        })
-                    } else {
-                        match hm.iter().map(|(k,v)| (k.as_str(), v)).next().unwrap() {
-                            #(#variant_match)*
-                            _ => Err(#crate_path::FromAutomergeError::WrongType {
-                                found: value.clone(),
-                                expected: format!("a non-unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
-                            })
-                        }
-                    }
-                } else if let automerge::Value::Primitive(automerge::Primitive::Str(s)) = value {
-                    match s.as_str() {
-                        #(#unit_variant_match)*
-                        _ => Err(#crate_path::FromAutomergeError::WrongType {
-                            found: value.clone(),
-                            expected: format!("a unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
-                        })
-                    }
-                } else {
-                    Err(#crate_path::FromAutomergeError::WrongType {
-                        found: value.clone(),
-                        expected: "a map".to_owned(),
+        if let automerge::Value::Map(hm) = value {
+            if hm.len() != 1 {
+                Err(#crate_path::FromAutomergeError::WrongType {
+                    found: #crate_path::ValueKind::from(value),
+                    expected: "a map with one item".to_owned(),
+                })
+            } else {
+                match hm.iter().map(|(k,v)| (k.as_str(), v)).next().unwrap() {
+                    #(#variant_match)*
+                    _ => Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ValueKind::from(value),
+                        expected: format!("a non-unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
                     })
                 }
             }
+        } else if let automerge::Value::Primitive(automerge::Primitive::Str(s)) = value {
+            match s.as_str() {
+                #(#unit_variant_match)*
+                _ => Err(#crate_path::FromAutomergeError::WrongType {
+                    found: #crate_path::ValueKind::from(value),
+                    expected: format!("a unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
+                })
+            }
+        } else {
+            Err(#crate_path::FromAutomergeError::WrongType {
+                found: #crate_path::ValueKind::from(value),
+                expected: "a map".to_owned(),
+            })
         }
     }
 }
 
-fn get_representation_type(
-    attrs: &[Attribute],
-    field_ty: &Type,
-    value_for_field: &TokenStream,
-    crate_path: &TokenStream,
-) -> TokenStream {
+/// Find the representation requested for a field through its attributes, if any.
+pub fn representation(attrs: &[Attribute]) -> Option<String> {
     let mut ty = None;
     for a in attrs {
         match a.parse_meta().unwrap() {
@@ -120,54 +151,94 @@ fn get_representation_type(
             }
         }
     }
-    match ty.map(|s| s.to_lowercase()).as_deref() {
+    ty.map(|s| s.to_lowercase())
+}
+
+pub fn has_representation(attrs: &[Attribute]) -> bool {
+    matches!(
+        representation(attrs).as_deref(),
+        Some("text") | Some("counter") | Some("timestamp")
+    )
+}
+
+/// Decode a field from its (possibly missing) value, giving a `Result` for the field type.
+pub fn get_representation_type(
+    attrs: &[Attribute],
+    field_ty: &Type,
+    value_for_field: &TokenStream,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    decode_field(attrs, field_ty, value_for_field, crate_path, None)
+}
+
+/// Decode a `value` of the given type, borrowing from it for `lifetime` if given.
+fn decode_value(
+    field_ty: &Type,
+    crate_path: &TokenStream,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream {
+    if let Some(lifetime) = lifetime {
+        quote! {
+            <#field_ty as #crate_path::FromAutomergeRef<#lifetime>>::from_automerge_ref(value)
+        }
+    } else {
+        quote! {
+            <#field_ty>::from_automerge(value)
+        }
+    }
+}
+
+fn decode_field(
+    attrs: &[Attribute],
+    field_ty: &Type,
+    value_for_field: &TokenStream,
+    crate_path: &TokenStream,
+    lifetime: Option<&Lifetime>,
+) -> TokenStream {
+    match representation(attrs).as_deref() {
         Some("text") => {
             quote! {
                 if let Some(value) = #value_for_field {
-                    <#crate_path::Text>::from_automerge(value)?.0.into_iter().map(|s|s.to_string()).collect()
+                    <#crate_path::Text>::from_automerge(value)
+                        .map(|text| text.graphemes().iter().map(|s| s.to_string()).collect())
                 } else {
-                    <#field_ty>::default()
+                    Ok(<#field_ty>::default())
                 }
             }
         }
         Some("counter") => {
             quote! {
-                if let Some(value) = #value_for_field {
-                    if let automerge::Value::Primitive(automerge::Primitive::Counter(i)) = value {
-                        *i
-                    } else {
-                        return Err(#crate_path::FromAutomergeError::WrongType {
-                            found: value.clone(),
-                            expected: "a primitive counter".to_owned(),
-                        })
-                    }
-                } else {
-                    <#field_ty>::default()
+                match #value_for_field {
+                    Some(automerge::Value::Primitive(automerge::Primitive::Counter(i))) => Ok(*i),
+                    Some(value) => Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ValueKind::from(value),
+                        expected: "a primitive counter".to_owned(),
+                    }),
+                    None => Ok(<#field_ty>::default()),
                 }
             }
         }
         Some("timestamp") => {
             quote! {
-                if let Some(value) = #value_for_field {
-                    if let automerge::Value::Primitive(automerge::Primitive::Timestamp(i)) = value {
-                        *i
-                    } else {
-                        return Err(#crate_path::FromAutomergeError::WrongType {
-                            found: value.clone(),
-                            expected: "a primitive timestamp".to_owned(),
-                        })
+                match #value_for_field {
+                    Some(automerge::Value::Primitive(automerge::Primitive::Timestamp(i))) => {
+                        <#field_ty as #crate_path::AsTimestamp>::from_timestamp(*i)
                     }
-                } else {
-                    <#field_ty>::default()
+                    Some(value) => Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ValueKind::from(value),
+                        expected: "a primitive timestamp".to_owned(),
+                    }),
+                    None => Ok(<#field_ty>::default()),
                 }
             }
         }
         _ => {
+            let decode = decode_value(field_ty, crate_path, lifetime);
             quote! {
                 if let Some(value) = #value_for_field {
-                    <#field_ty>::from_automerge(value)?
+                    #decode
                 } else {
-                    <#field_ty>::default()
+                    Ok(<#field_ty>::default())
                 }
             }
         }
@@ -178,11 +249,14 @@ fn fields_from_automerge(
     fields: &Fields,
     variant_name: Option<Ident>,
     crate_path: &TokenStream,
+    lifetime: Option<&Lifetime>,
 ) -> TokenStream {
-    let ty_name = if let Some(name) = variant_name {
-        quote! {Self::#name}
+    // errors in variant fields are nested under the variant's key
+    let (ty_name, variant_path) = if let Some(name) = variant_name {
+        let name_string = name.to_string();
+        (quote! {Self::#name}, quote! {.at_key(#name_string)})
     } else {
-        quote! {Self}
+        (quote! {Self}, quote! {})
     };
     match fields {
         Fields::Named(n) => {
@@ -194,10 +268,9 @@ fn fields_from_automerge(
                 let value_for_field = quote! {
                     hm.get(#field_name_string)
                 };
-                let repr =
-                    get_representation_type(&f.attrs, field_ty, &value_for_field, crate_path);
+                let repr = decode_field(&f.attrs, field_ty, &value_for_field, crate_path, lifetime);
                 quote! {
-                    #field_name: #repr,
+                    #field_name: {#repr}.map_err(|e| e.at_key(#field_name_string)#variant_path)?,
                 }
             });
             quote! {
@@ -207,7 +280,7 @@ fn fields_from_automerge(
                     })
                 } else {
                     Err(#crate_path::FromAutomergeError::WrongType {
-                        found: value.clone(),
+                        found: #crate_path::ValueKind::from(value),
                         expected: "a map".to_owned(),
                     })
                 }
@@ -216,10 +289,9 @@ fn fields_from_automerge(
         Fields::Unnamed(u) => {
             if u.unnamed.len() == 1 {
                 let field = u.unnamed.first().unwrap();
-                let field_ty = &field.ty;
-
+                let decode = decode_value(&field.ty, crate_path, lifetime);
                 quote! {
-                    <#field_ty>::from_automerge(value).map(#ty_name)
+                    #decode.map(#ty_name).map_err(|e| e #variant_path)
                 }
             } else {
                 let fields = u.unnamed.iter().enumerate().map(|(i, f)| {
@@ -230,9 +302,9 @@ fn fields_from_automerge(
                         seq.get(#field_name)
                     };
                     let repr =
-                        get_representation_type(&f.attrs, field_ty, &value_for_field, crate_path);
+                        decode_field(&f.attrs, field_ty, &value_for_field, crate_path, lifetime);
                     quote! {
-                        #repr,
+                        {#repr}.map_err(|e| e.at_index(#i)#variant_path)?,
                     }
                 });
                 quote! {
@@ -242,7 +314,7 @@ fn fields_from_automerge(
                         ))
                     } else {
                         Err(#crate_path::FromAutomergeError::WrongType {
-                            found: value.clone(),
+                            found: #crate_path::ValueKind::from(value),
                             expected: "a list".to_owned(),
                         })
                     }
@@ -255,7 +327,7 @@ fn fields_from_automerge(
                     Ok(#ty_name)
                 } else {
                     Err(#crate_path::FromAutomergeError::WrongType {
-                        found: value.clone(),
+                        found: #crate_path::ValueKind::from(value),
                         expected: "a primitive null".to_owned(),
                     })
                 }
@@ -0,0 +1,69 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{Data, DataStruct, DeriveInput, Fields};
+
+use crate::utils;
+
+pub fn map_key(input: &DeriveInput) -> TokenStream {
+    let crate_path = utils::crate_path(input);
+    let t_name = &input.ident;
+    let t_name_string = t_name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let (to_key, from_key) = match &input.data {
+        Data::Enum(e) if e.variants.iter().all(|v| matches!(v.fields, Fields::Unit)) => {
+            let to_keys = e.variants.iter().map(|v| {
+                let v_name = &v.ident;
+                let v_name_string = v_name.to_string();
+                quote! { Self::#v_name => #v_name_string.to_owned(), }
+            });
+            let from_keys = e.variants.iter().map(|v| {
+                let v_name = &v.ident;
+                let v_name_string = v_name.to_string();
+                quote! { #v_name_string => Ok(Self::#v_name), }
+            });
+            (
+                quote! {
+                    match self {
+                        #(#to_keys)*
+                    }
+                },
+                quote! {
+                    match key {
+                        #(#from_keys)*
+                        _ => Err(#crate_path::FromAutomergeError::Parse {
+                            value: key.to_owned(),
+                            target_type: #t_name_string,
+                        }),
+                    }
+                },
+            )
+        }
+        Data::Struct(DataStruct {
+            fields: Fields::Unnamed(u),
+            ..
+        }) if u.unnamed.len() == 1 => {
+            let ty = &u.unnamed.first().unwrap().ty;
+            (
+                quote! { <#ty as #crate_path::MapKey>::to_key(&self.0) },
+                quote! { <#ty as #crate_path::MapKey>::from_key(key).map(Self) },
+            )
+        }
+        _ => {
+            return quote_spanned! {input.ident.span()=>
+                compile_error!("MapKey can only be derived for enums with unit variants and newtype structs");
+            }
+        }
+    };
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::MapKey for #t_name #ty_generics #where_clause {
+            fn to_key(&self) -> ::std::string::String {
+                #to_key
+            }
+
+            fn from_key(key: &str) -> ::std::result::Result<Self, #crate_path::FromAutomergeError> {
+                #from_key
+            }
+        }
+    }
+}
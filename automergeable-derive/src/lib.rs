@@ -6,14 +6,24 @@ use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
 mod from;
+mod key;
+mod patch;
+mod reconcile;
+mod root;
 mod to;
 mod utils;
+mod view;
 
 /// Derive the [`Automergeable`](automergeable_traits::Automergeable) trait.
 ///
 /// Covers:
 /// - conversion into an automerge `Value`
 /// - conversion from an automerge `Value`
+/// - applying patches to the value in place
+/// - decoding from a view of an `automergeable-view` document, with the `view` feature, see
+///   [`FromView`]
+/// - [`RootAutomergeable`](automergeable_traits::RootAutomergeable) for structs with named
+///   fields, so they can be the root of a document
 ///
 /// For attributes available, see [`ToAutomerge`] and [`FromAutomerge`] documentation.
 #[proc_macro_derive(Automergeable, attributes(automergeable))]
@@ -21,9 +31,23 @@ pub fn automergeable(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let to = to::to_automerge(&input);
     let from = from::from_automerge(&input);
+    let patch = patch::apply_patch(&input);
+    let view = if cfg!(feature = "view") {
+        Some(view::from_view(&input))
+    } else {
+        None
+    };
+    let root = if root::is_map(&input) {
+        Some(root::root_automergeable(&input))
+    } else {
+        None
+    };
     (quote! {
         #to
         #from
+        #patch
+        #view
+        #root
     })
     .into()
 }
@@ -33,16 +57,31 @@ pub fn automergeable(input: TokenStream) -> TokenStream {
 /// # Supported attributes
 ///
 /// `#[automergeable(representation = "text")]`: Converts a [`String`] to an [`automerge::Value::Text`] type, separating the string into grapheme clusters.
+/// The whole string is compared on each change, use a [`Text`](automergeable_traits::Text) field
+/// instead for text that is edited in place.
 ///
 /// `#[automergeable(representation = "counter")]`: Converts an [`i64`] into an [`automerge::Primitive::Counter`] type.
+/// A [`Counter`](automergeable_traits::Counter) field does the same without the attribute, and
+/// also works inside collections such as `Vec` and `HashMap`.
 ///
-/// `#[automergeable(representation = "timestamp")]`: Converts an [`i64`] into an [`automerge::Primitive::Timestamp`] type.
+/// `#[automergeable(representation = "timestamp")]`: Converts an [`i64`] (or any other
+/// [`AsTimestamp`](automergeable_traits::AsTimestamp) type) into an [`automerge::Primitive::Timestamp`] type.
 #[proc_macro_derive(ToAutomerge, attributes(automergeable))]
 pub fn to_automerge(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     to::to_automerge(&input).into()
 }
 
+/// Derive the [`TryToAutomerge`](automergeable_traits::TryToAutomerge) trait.
+///
+/// This is for types with fields that only implement [`TryToAutomerge`], those deriving
+/// [`ToAutomerge`] get it already. Supports the same attributes as [`ToAutomerge`].
+#[proc_macro_derive(TryToAutomerge, attributes(automergeable))]
+pub fn try_to_automerge(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    to::try_to_automerge(&input).into()
+}
+
 /// Derive the [`FromAutomerge`](automergeable_traits::FromAutomerge) trait.
 ///
 /// # Supported attributes
@@ -51,9 +90,86 @@ pub fn to_automerge(input: TokenStream) -> TokenStream {
 ///
 /// `#[automergeable(representation = "counter")]`: Converts an [`automerge::Primitive::Counter`] type to an [`i64`].
 ///
-/// `#[automergeable(representation = "timestamp")]`: Converts an [`automerge::Primitive::Timestamp`] type to an [`i64`].
+/// `#[automergeable(representation = "timestamp")]`: Converts an [`automerge::Primitive::Timestamp`] type to an [`i64`] (or any
+/// other [`AsTimestamp`](automergeable_traits::AsTimestamp) type).
 #[proc_macro_derive(FromAutomerge, attributes(automergeable))]
 pub fn from_automerge(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     from::from_automerge(&input).into()
 }
+
+/// Derive the [`FromAutomergeRef`](automergeable_traits::FromAutomergeRef) trait, decoding
+/// without copying strings and bytes out of the value.
+///
+/// The value is borrowed for the first lifetime parameter of the type, so fields such as
+/// `&'a str` and `Vec<&'a [u8]>` point into it. Supports the same attributes as [`FromAutomerge`].
+#[proc_macro_derive(FromAutomergeRef, attributes(automergeable))]
+pub fn from_automerge_ref(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    from::from_automerge_ref(&input).into()
+}
+
+/// Derive the `FromView` trait, decoding straight from a view of an `automergeable-view`
+/// document.
+///
+/// Reads the same representation as [`FromAutomerge`], with text as a `TextView` and counters and
+/// timestamps as their scalar values. Supports the same attributes as [`FromAutomerge`].
+///
+/// This needs the `automergeable-view` feature of `automergeable`, which also enables the `view`
+/// feature here so that [`Automergeable`] derives it too.
+#[proc_macro_derive(FromView, attributes(automergeable))]
+pub fn from_view(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    view::from_view(&input).into()
+}
+
+/// Derive the `Reconcile` trait, writing the value into a transaction on an `automergeable-view`
+/// document with as few operations as possible.
+///
+/// Writes the same representation as [`ToAutomerge`], only touching values that changed. Fields
+/// with `representation = "counter"` are incremented by the difference rather than overwritten.
+/// Supports the same attributes as [`ToAutomerge`].
+///
+/// This isn't part of [`Automergeable`] and needs the `automergeable-view` feature of
+/// `automergeable`.
+#[proc_macro_derive(Reconcile, attributes(automergeable))]
+pub fn reconcile(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    self::reconcile::reconcile(&input).into()
+}
+
+/// Derive the [`ApplyPatch`](automergeable_traits::ApplyPatch) trait.
+///
+/// Structs with named fields only update the fields referenced by the patch, other types are
+/// decoded in full.
+#[proc_macro_derive(ApplyPatch, attributes(automergeable))]
+pub fn apply_patch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    patch::apply_patch(&input).into()
+}
+
+/// Derive the [`RootAutomergeable`](automergeable_traits::RootAutomergeable) marker trait.
+///
+/// Only needed when deriving the conversion traits individually, [`Automergeable`] already
+/// derives this for structs with named fields. Using it on any other type is a compile error.
+#[proc_macro_derive(RootAutomergeable, attributes(automergeable))]
+pub fn root_automergeable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    root::root_automergeable(&input).into()
+}
+
+/// Derive the [`MapKey`](automergeable_traits::MapKey) trait, so the type can be used as the key
+/// of a map or an element of a set.
+///
+/// Enums with only unit variants are keyed by the variant name. Newtype structs are keyed like
+/// their field. Using it on any other type is a compile error.
+///
+/// # Supported attributes
+///
+/// `#[automergeable(crate_path = "path")]`: Where to find the `automergeable` crate, for when it
+/// has been renamed or re-exported.
+#[proc_macro_derive(MapKey, attributes(automergeable))]
+pub fn map_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    key::map_key(&input).into()
+}
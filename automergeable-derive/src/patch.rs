@@ -0,0 +1,154 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DataStruct, DeriveInput, Fields};
+
+use crate::{from, utils};
+
+pub fn apply_patch(input: &DeriveInput) -> TokenStream {
+    let crate_path = utils::crate_path(input);
+    let t_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let (body, take_splices) = match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => (
+            fields_apply_patch(fields, &crate_path),
+            fields_take_splices(fields, &crate_path),
+        ),
+        // enums can change variant so just use the default of decoding in full
+        Data::Enum(_) => (None, None),
+        Data::Union(_) => panic!("this derive macro only works on structs and enums"),
+    };
+    let body = body.map(|body| {
+        quote! {
+            #[allow(unused_variables)]
+            fn apply_patch(
+                &mut self,
+                diff: &#crate_path::patch::Diff,
+                value: &automerge::Value,
+            ) -> ::std::result::Result<(), #crate_path::FromAutomergeError> {
+                #body
+            }
+        }
+    });
+    let take_splices = take_splices.map(|take_splices| {
+        quote! {
+            fn take_splices(
+                &mut self,
+                path: &mut ::std::vec::Vec<#crate_path::PathSegment>,
+                splices: &mut #crate_path::TextSplices,
+            ) {
+                #take_splices
+            }
+        }
+    });
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::ApplyPatch for #t_name #ty_generics #where_clause {
+            #body
+            #take_splices
+        }
+    }
+}
+
+fn fields_take_splices(fields: &Fields, crate_path: &TokenStream) -> Option<TokenStream> {
+    match fields {
+        Fields::Named(n) => {
+            // fields with special representations aren't stored as their own type so are skipped
+            let fields = n
+                .named
+                .iter()
+                .filter(|f| !from::has_representation(&f.attrs))
+                .map(|f| {
+                    let field_name = f.ident.as_ref().unwrap();
+                    let field_name_string = format_ident!("{}", field_name).to_string();
+                    quote! {
+                        path.push(#crate_path::PathSegment::Key(#field_name_string.into()));
+                        #crate_path::ApplyPatch::take_splices(&mut self.#field_name, path, splices);
+                        path.pop();
+                    }
+                });
+            Some(quote! { #(#fields)* })
+        }
+        Fields::Unnamed(u) => {
+            let field = u.unnamed.first()?;
+            if u.unnamed.len() == 1 && !from::has_representation(&field.attrs) {
+                Some(quote! {
+                    #crate_path::ApplyPatch::take_splices(&mut self.0, path, splices)
+                })
+            } else {
+                None
+            }
+        }
+        Fields::Unit => None,
+    }
+}
+
+fn fields_apply_patch(fields: &Fields, crate_path: &TokenStream) -> Option<TokenStream> {
+    match fields {
+        Fields::Named(n) => {
+            let fields = n.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap();
+                let field_ty = &f.ty;
+                let field_name_string = format_ident!("{}", field_name).to_string();
+                let apply = if from::has_representation(&f.attrs) {
+                    // special representations aren't the field's own type so decode them in full
+                    let value_for_field = quote! { hm.get(#field_name_string) };
+                    let repr = from::get_representation_type(
+                        &f.attrs,
+                        field_ty,
+                        &value_for_field,
+                        crate_path,
+                    );
+                    quote! {
+                        self.#field_name = {#repr}.map_err(|e| e.at_key(#field_name_string))?;
+                    }
+                } else {
+                    quote! {
+                        let result = match (hm.get(#field_name_string), #crate_path::patch::resolve_diff(diffs)) {
+                            (Some(value), Some(diff)) => {
+                                #crate_path::ApplyPatch::apply_patch(&mut self.#field_name, diff, value)
+                            }
+                            (Some(value), None) => {
+                                <#field_ty as #crate_path::FromAutomerge>::from_automerge(value)
+                                    .map(|value| self.#field_name = value)
+                            }
+                            (None, _) => {
+                                self.#field_name = <#field_ty>::default();
+                                Ok(())
+                            }
+                        };
+                        result.map_err(|e| e.at_key(#field_name_string))?;
+                    }
+                };
+                quote! {
+                    #field_name_string => { #apply }
+                }
+            });
+            Some(quote! {
+                if let (#crate_path::patch::Diff::Map(map_diff), automerge::Value::Map(hm)) = (diff, value) {
+                    for (key, diffs) in &map_diff.props {
+                        match key.as_str() {
+                            #(#fields)*
+                            _ => {}
+                        }
+                    }
+                    Ok(())
+                } else {
+                    *self = <Self as #crate_path::FromAutomerge>::from_automerge(value)?;
+                    Ok(())
+                }
+            })
+        }
+        Fields::Unnamed(u) => {
+            let field = u.unnamed.first()?;
+            if u.unnamed.len() == 1 && !from::has_representation(&field.attrs) {
+                // newtypes are transparent so pass straight through
+                Some(quote! {
+                    #crate_path::ApplyPatch::apply_patch(&mut self.0, diff, value)
+                })
+            } else {
+                None
+            }
+        }
+        Fields::Unit => None,
+    }
+}
@@ -0,0 +1,216 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DataEnum, DataStruct, DeriveInput, Fields};
+
+use crate::{from, utils};
+
+pub fn reconcile(input: &DeriveInput) -> TokenStream {
+    let crate_path = utils::crate_path(input);
+    let t_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let failed = quote! {Err(error) => return Err(error.into())};
+    let slot_body = reconcile_body(
+        input,
+        &quote! {reconcile_slot(&value, slot)},
+        &failed,
+        &crate_path,
+    );
+    let view_body = reconcile_body(
+        input,
+        &quote! {reconcile(&value, view)},
+        &failed,
+        &crate_path,
+    );
+    // a field that can't be converted can't match what is stored
+    let matches_body = reconcile_body(
+        input,
+        &quote! {matches(&value, current)},
+        &quote! {Err(_) => return false},
+        &crate_path,
+    );
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::Reconcile for #t_name #ty_generics #where_clause {
+            fn reconcile_slot(&self, slot: #crate_path::Slot<'_, '_, '_>) -> ::std::result::Result<(), #crate_path::ReconcileError> {
+                #slot_body
+            }
+
+            fn reconcile(&self, view: &mut #crate_path::reconcile::MutableView<'_, '_>) -> ::std::result::Result<(), #crate_path::ReconcileError> {
+                #view_body
+            }
+
+            fn matches(&self, current: &#crate_path::reconcile::View<'_, #crate_path::reconcile::Transaction<'_>>) -> bool {
+                #matches_body
+            }
+        }
+    }
+}
+
+/// Build the reconciliation of `self`, calling `method` on the value it is stored as.
+///
+/// `failed` is the match arm for a field that can't be converted to what it is stored as.
+fn reconcile_body(
+    input: &DeriveInput,
+    method: &TokenStream,
+    failed: &TokenStream,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            let value = fields_value(fields, true, failed, crate_path);
+            quote! {
+                #value
+                #crate_path::Reconcile::#method
+            }
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            let variants = variants.iter().map(|v| {
+                let v_name = &v.ident;
+                let v_name_string = v_name.to_string();
+                let pattern = match &v.fields {
+                    Fields::Named(n) => {
+                        let names = n.named.iter().map(|n| &n.ident);
+                        quote! {{ #(#names,)* }}
+                    }
+                    Fields::Unnamed(u) => {
+                        let items = (0..u.unnamed.len())
+                            .map(|i| Ident::new(&format!("f{}", i), Span::call_site()));
+                        quote! {( #(#items,)* )}
+                    }
+                    Fields::Unit => quote! {},
+                };
+                let value = if v.fields.is_empty() {
+                    quote! {
+                        let value = #v_name_string;
+                    }
+                } else {
+                    let fields = fields_value(&v.fields, false, failed, crate_path);
+                    quote! {
+                        #fields
+                        let value = #crate_path::reconcile::Variant(#v_name_string, &value);
+                    }
+                };
+                quote! {
+                    Self::#v_name#pattern => {
+                        #value
+                        #crate_path::Reconcile::#method
+                    }
+                }
+            });
+            quote! {
+                match self {
+                    #(#variants)*
+                }
+            }
+        }
+        Data::Union(_) => panic!("this derive macro only works on structs with named fields"),
+    }
+}
+
+/// A reference to the value a field is stored as, given a reference to the field.
+fn field_value(
+    attrs: &[Attribute],
+    field: &TokenStream,
+    failed: &TokenStream,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    match from::representation(attrs).as_deref() {
+        Some("text") => quote! {
+            &#crate_path::Text::from(::std::convert::AsRef::<str>::as_ref(#field))
+        },
+        Some("counter") => quote! {
+            &#crate_path::Counter::new(*#field)
+        },
+        Some("timestamp") => quote! {
+            &#crate_path::Timestamp::from_millis(match #crate_path::AsTimestamp::to_timestamp(#field) {
+                Ok(millis) => millis,
+                #failed
+            })
+        },
+        _ => quote! { #field },
+    }
+}
+
+/// Bind `value` to the value the fields are stored as, the fields being on `self` if `is_struct`
+/// and bound by a pattern otherwise.
+fn fields_value(
+    fields: &Fields,
+    is_struct: bool,
+    failed: &TokenStream,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    match fields {
+        Fields::Named(n) => {
+            let bindings = n.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap();
+                let binding = format_ident!("field_{}", field_name);
+                let field = if is_struct {
+                    quote! {&self.#field_name}
+                } else {
+                    quote! {#field_name}
+                };
+                let value = field_value(&f.attrs, &field, failed, crate_path);
+                quote! {
+                    let #binding = #value;
+                }
+            });
+            let entries = n.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap();
+                let field_name_string = field_name.to_string();
+                let binding = format_ident!("field_{}", field_name);
+                quote! {
+                    (#field_name_string, #binding as &dyn #crate_path::Reconcile),
+                }
+            });
+            quote! {
+                #(#bindings)*
+                let entries = [#(#entries)*];
+                let value = #crate_path::reconcile::Fields(&entries);
+            }
+        }
+        Fields::Unnamed(u) => {
+            let field = |i: usize| {
+                if is_struct {
+                    let index = syn::Index::from(i);
+                    quote! {&self.#index}
+                } else {
+                    let f = Ident::new(&format!("f{}", i), Span::call_site());
+                    quote! {#f}
+                }
+            };
+            if u.unnamed.len() == 1 {
+                let value = field_value(
+                    &u.unnamed.first().unwrap().attrs,
+                    &field(0),
+                    failed,
+                    crate_path,
+                );
+                quote! {
+                    let value = #value;
+                }
+            } else {
+                let bindings = u.unnamed.iter().enumerate().map(|(i, f)| {
+                    let binding = format_ident!("field_{}", i);
+                    let value = field_value(&f.attrs, &field(i), failed, crate_path);
+                    quote! {
+                        let #binding = #value;
+                    }
+                });
+                let items = (0..u.unnamed.len()).map(|i| {
+                    let binding = format_ident!("field_{}", i);
+                    quote! {
+                        #binding as &dyn #crate_path::Reconcile,
+                    }
+                });
+                quote! {
+                    #(#bindings)*
+                    let items = [#(#items)*];
+                    let value = #crate_path::reconcile::Items(&items);
+                }
+            }
+        }
+        Fields::Unit => quote! {
+            let value = ();
+        },
+    }
+}
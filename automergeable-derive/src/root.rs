@@ -0,0 +1,31 @@
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{Data, DataStruct, DeriveInput, Fields};
+
+use crate::utils;
+
+/// Whether the type is represented as a map, so can be the root of a document.
+pub fn is_map(input: &DeriveInput) -> bool {
+    matches!(
+        &input.data,
+        Data::Struct(DataStruct {
+            fields: Fields::Named(_),
+            ..
+        })
+    )
+}
+
+pub fn root_automergeable(input: &DeriveInput) -> TokenStream {
+    if !is_map(input) {
+        return quote_spanned! {input.ident.span()=>
+            compile_error!("only structs with named fields are maps, wrap other types in `Rooted` to use them as a root");
+        };
+    }
+    let crate_path = utils::crate_path(input);
+    let t_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::RootAutomergeable for #t_name #ty_generics #where_clause {}
+    }
+}
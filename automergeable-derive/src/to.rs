@@ -5,35 +5,135 @@ use syn::{
     Fields, Lit, Meta, NestedMeta, Variant,
 };
 
-use crate::utils;
+use crate::{from, utils};
 
 pub fn to_automerge(input: &DeriveInput) -> TokenStream {
-    match &input.data {
-        Data::Struct(DataStruct { fields, .. }) => to_automerge_struct(input, fields),
-        Data::Enum(DataEnum { variants, .. }) => to_automerge_enum(input, variants),
-        Data::Union(_) => panic!("this derive macro only works on structs with named fields"),
-    }
-}
-
-fn to_automerge_struct(input: &DeriveInput, fields: &Fields) -> TokenStream {
     let crate_path = utils::crate_path(input);
     let t_name = &input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
-    let fields_to_automerge = fields_to_automerge(fields, true, &crate_path);
+    let body = to_automerge_body(input, false, &crate_path);
+    let checked_body = to_automerge_body(input, true, &crate_path);
+    let changed = to_automerge_changed(input, &crate_path).map(|changed| {
+        quote! {
+            #[allow(unused_variables)]
+            fn to_automerge_changed(
+                &self,
+                old: &Self,
+                path: &mut ::std::vec::Vec<#crate_path::PathSegment>,
+                changed: &mut #crate_path::ChangedValues,
+            ) -> ::std::result::Result<(), #crate_path::ToAutomergeError> {
+                #changed
+                Ok(())
+            }
+        }
+    });
     quote! {
         #[automatically_derived]
         impl #impl_generics #crate_path::ToAutomerge for #t_name #ty_generics #where_clause {
             fn to_automerge(&self) -> automerge::Value {
-                #fields_to_automerge
+                #body
             }
+
+            fn to_automerge_checked(&self) -> ::std::result::Result<automerge::Value, #crate_path::ToAutomergeError> {
+                Ok({ #checked_body })
+            }
+
+            #changed
         }
     }
 }
 
-fn to_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma>) -> TokenStream {
+pub fn try_to_automerge(input: &DeriveInput) -> TokenStream {
     let crate_path = utils::crate_path(input);
     let t_name = &input.ident;
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = to_automerge_body(input, true, &crate_path);
+    let changed = to_automerge_changed(input, &crate_path).map(|changed| {
+        quote! {
+            #[allow(unused_variables)]
+            fn try_to_automerge_changed(
+                &self,
+                old: &Self,
+                path: &mut ::std::vec::Vec<#crate_path::PathSegment>,
+                changed: &mut #crate_path::ChangedValues,
+            ) -> ::std::result::Result<(), #crate_path::ToAutomergeError> {
+                #changed
+                Ok(())
+            }
+        }
+    });
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::TryToAutomerge for #t_name #ty_generics #where_clause {
+            fn try_to_automerge(&self) -> ::std::result::Result<automerge::Value, #crate_path::ToAutomergeError> {
+                Ok({ #body })
+            }
+
+            #changed
+        }
+    }
+}
+
+/// Build the conversion of the fields of a struct that may have changed from `old`.
+///
+/// Enums can change variant so are converted in full by the default.
+fn to_automerge_changed(input: &DeriveInput, crate_path: &TokenStream) -> Option<TokenStream> {
+    match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(n),
+            ..
+        }) => {
+            let fields = n.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap();
+                let field_name_string = format_ident!("{}", field_name).to_string();
+                let change = if from::has_representation(&f.attrs) {
+                    // special representations aren't the field's own type so are converted in full
+                    let repr =
+                        get_representation_type(&f.attrs, &quote! {self.#field_name}, true, crate_path);
+                    quote! {
+                        changed.push(path, ::std::option::Option::Some(#repr));
+                    }
+                } else {
+                    quote! {
+                        #crate_path::TryToAutomerge::try_to_automerge_changed(&self.#field_name, &old.#field_name, path, changed)?;
+                    }
+                };
+                quote! {
+                    path.push(#crate_path::PathSegment::Key(#field_name_string.into()));
+                    #change
+                    path.pop();
+                }
+            });
+            Some(quote! { #(#fields)* })
+        }
+        Data::Struct(DataStruct {
+            fields: Fields::Unnamed(u),
+            ..
+        }) if u.unnamed.len() == 1 && !from::has_representation(&u.unnamed[0].attrs) => {
+            Some(quote! {
+                #crate_path::TryToAutomerge::try_to_automerge_changed(&self.0, &old.0, path, changed)?;
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Build the conversion of `self` into a value, returning early with any errors if `checked`.
+fn to_automerge_body(input: &DeriveInput, checked: bool, crate_path: &TokenStream) -> TokenStream {
+    match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            fields_to_automerge(fields, true, checked, crate_path)
+        }
+        Data::Enum(DataEnum { variants, .. }) => to_automerge_enum(variants, checked, crate_path),
+        Data::Union(_) => panic!("this derive macro only works on structs with named fields"),
+    }
+}
+
+fn to_automerge_enum(
+    variants: &Punctuated<Variant, Comma>,
+    checked: bool,
+    crate_path: &TokenStream,
+) -> TokenStream {
     let variants = variants.iter().map(|v| {
         let v_name = &v.ident;
         let fields = match &v.fields {
@@ -57,7 +157,7 @@ fn to_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma>)
         };
         let v_name_string = v_name.to_string();
         if let Some(fields) = fields {
-            let fields_to_automerge = fields_to_automerge(&v.fields, false, &crate_path);
+            let fields_to_automerge = fields_to_automerge(&v.fields, false, checked, crate_path);
             quote! {
                 Self::#v_name#fields => {
                     let mut outer = ::std::collections::HashMap::new();
@@ -75,13 +175,8 @@ fn to_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma>)
         }
     });
     quote! {
-        #[automatically_derived]
-        impl #impl_generics #crate_path::ToAutomerge for #t_name #ty_generics #where_clause {
-            fn to_automerge(&self) -> automerge::Value {
-                match self {
-                    #(#variants)*
-                }
-            }
+        match self {
+            #(#variants)*
         }
     }
 }
@@ -89,6 +184,7 @@ fn to_automerge_enum(input: &DeriveInput, variants: &Punctuated<Variant, Comma>)
 fn get_representation_type(
     attrs: &[Attribute],
     field_name: &TokenStream,
+    checked: bool,
     crate_path: &TokenStream,
 ) -> TokenStream {
     let mut ty = None;
@@ -127,13 +223,35 @@ fn get_representation_type(
             quote! { automerge::Value::Primitive(automerge::Primitive::Counter(#field_name)) }
         }
         Some("timestamp") => {
-            quote! { automerge::Value::Primitive(automerge::Primitive::Timestamp(#field_name)) }
+            let timestamp = if checked {
+                quote! { #field_name.to_timestamp()? }
+            } else {
+                quote! {
+                    match #field_name.to_timestamp() {
+                        Ok(timestamp) => timestamp,
+                        Err(e) => panic!("{}", e),
+                    }
+                }
+            };
+            quote! {{
+                use #crate_path::AsTimestamp as _;
+                automerge::Value::Primitive(automerge::Primitive::Timestamp(#timestamp))
+            }}
         }
+        _ if checked => quote! {{
+            use #crate_path::TryToAutomerge as _;
+            #field_name.try_to_automerge()?
+        }},
         _ => quote! { #field_name.to_automerge() },
     }
 }
 
-fn fields_to_automerge(fields: &Fields, is_struct: bool, crate_path: &TokenStream) -> TokenStream {
+fn fields_to_automerge(
+    fields: &Fields,
+    is_struct: bool,
+    checked: bool,
+    crate_path: &TokenStream,
+) -> TokenStream {
     match fields {
         Fields::Named(n) => {
             let fields = n.named.iter().map(|f| {
@@ -145,7 +263,7 @@ fn fields_to_automerge(fields: &Fields, is_struct: bool, crate_path: &TokenStrea
                 } else {
                     quote! {#field_name}
                 };
-                let repr = get_representation_type(&f.attrs, &field_name, crate_path);
+                let repr = get_representation_type(&f.attrs, &field_name, checked, crate_path);
                 quote! {
                     fields.insert(#field_name_string.into(), #repr);
                 }
@@ -166,7 +284,7 @@ fn fields_to_automerge(fields: &Fields, is_struct: bool, crate_path: &TokenStrea
                     let f = Ident::new(&format!("f{}", 0), Span::call_site());
                     quote! {#f}
                 };
-                let repr = get_representation_type(&field.attrs, &field_name, crate_path);
+                let repr = get_representation_type(&field.attrs, &field_name, checked, crate_path);
                 quote! {
                     #repr
                 }
@@ -179,7 +297,7 @@ fn fields_to_automerge(fields: &Fields, is_struct: bool, crate_path: &TokenStrea
                         let f = Ident::new(&format!("f{}", i), Span::call_site());
                         quote! {#f}
                     };
-                    let repr = get_representation_type(&f.attrs, &field_name, crate_path);
+                    let repr = get_representation_type(&f.attrs, &field_name, checked, crate_path);
                     quote! {
                         fields.push(#repr);
                     }
@@ -0,0 +1,199 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    punctuated::Punctuated, token::Comma, Attribute, Data, DataEnum, DataStruct, DeriveInput,
+    Fields, Ident, Type, Variant,
+};
+
+use crate::{from, utils};
+
+pub fn from_view(input: &DeriveInput) -> TokenStream {
+    let crate_path = utils::crate_path(input);
+    let t_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = match &input.data {
+        Data::Struct(DataStruct { fields, .. }) => fields_from_view(fields, None, &crate_path),
+        Data::Enum(DataEnum { variants, .. }) => from_view_enum(input, variants, &crate_path),
+        Data::Union(_) => panic!("this derive macro only works on structs"),
+    };
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics #crate_path::FromView for #t_name #ty_generics #where_clause {
+            fn from_view<__R: #crate_path::ReadView>(view: &__R) -> ::std::result::Result<Self, #crate_path::FromAutomergeError> {
+                #body
+            }
+        }
+    }
+}
+
+fn from_view_enum(
+    input: &DeriveInput,
+    variants: &Punctuated<Variant, Comma>,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    let t_name = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let variant_match = variants.iter().filter(|v| !v.fields.is_empty()).map(|v| {
+        let v_name = &v.ident;
+        let v_name_string = v_name.to_string();
+        let fields_from_view = fields_from_view(&v.fields, Some(v_name.clone()), crate_path);
+        quote! {
+            #v_name_string => {#fields_from_view}
+        }
+    });
+    let unit_variant_match = variants.iter().filter(|v| v.fields.is_empty()).map(|v| {
+        let v_name = &v.ident;
+        let v_name_string = v_name.to_string();
+        quote! {
+            #v_name_string => {Ok(Self::#v_name)}
+        }
+    });
+
+    quote! {
+        match #crate_path::ReadView::kind(view) {
+            #crate_path::ValueKind::Map => {
+                let keys = #crate_path::ReadView::keys(view);
+                if keys.len() != 1 {
+                    Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ReadView::kind(view),
+                        expected: "a map with one item".to_owned(),
+                    })
+                } else {
+                    let inner = #crate_path::ReadView::get_key(view, &keys[0])
+                        .expect("key from map to have a value");
+                    let view = &inner;
+                    match keys[0].as_str() {
+                        #(#variant_match)*
+                        _ => Err(#crate_path::FromAutomergeError::WrongType {
+                            found: #crate_path::ValueKind::Map,
+                            expected: format!("a non-unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
+                        })
+                    }
+                }
+            }
+            #crate_path::ValueKind::Str => {
+                let s = <::std::string::String as #crate_path::FromView>::from_view(view)?;
+                match s.as_str() {
+                    #(#unit_variant_match)*
+                    _ => Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ValueKind::Str,
+                        expected: format!("a unit variant of {}", std::any::type_name::<#t_name#ty_generics>()),
+                    })
+                }
+            }
+            found => Err(#crate_path::FromAutomergeError::WrongType {
+                found,
+                expected: "a map".to_owned(),
+            }),
+        }
+    }
+}
+
+/// Decode a field from its (possibly missing) view, giving a `Result` for the field type.
+fn decode_field(
+    attrs: &[Attribute],
+    field_ty: &Type,
+    view_for_field: &TokenStream,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    let decode = match from::representation(attrs).as_deref() {
+        Some("text") => quote! {
+            <#crate_path::Text as #crate_path::FromView>::from_view(view)
+                .map(|text| text.graphemes().iter().map(|s| s.to_string()).collect())
+        },
+        Some("counter") => quote! {
+            <#crate_path::Counter as #crate_path::FromView>::from_view(view)
+                .map(|counter| counter.value())
+        },
+        Some("timestamp") => quote! {
+            <#crate_path::Timestamp as #crate_path::FromView>::from_view(view)
+                .and_then(|t| <#field_ty as #crate_path::AsTimestamp>::from_timestamp(t.millis()))
+        },
+        _ => quote! {
+            <#field_ty as #crate_path::FromView>::from_view(view)
+        },
+    };
+    quote! {
+        match #view_for_field {
+            Some(view) => #decode,
+            None => Ok(<#field_ty>::default()),
+        }
+    }
+}
+
+fn fields_from_view(
+    fields: &Fields,
+    variant_name: Option<Ident>,
+    crate_path: &TokenStream,
+) -> TokenStream {
+    // errors in variant fields are nested under the variant's key
+    let (ty_name, variant_path) = if let Some(name) = variant_name {
+        let name_string = name.to_string();
+        (quote! {Self::#name}, quote! {.at_key(#name_string)})
+    } else {
+        (quote! {Self}, quote! {})
+    };
+    match fields {
+        Fields::Named(n) => {
+            let fields = n.named.iter().map(|f| {
+                let field_name = f.ident.as_ref().unwrap();
+                let field_name_string = field_name.to_string();
+                let view_for_field = quote! {
+                    #crate_path::ReadView::get_key(view, #field_name_string).as_ref()
+                };
+                let decode = decode_field(&f.attrs, &f.ty, &view_for_field, crate_path);
+                quote! {
+                    #field_name: {#decode}.map_err(|e| e.at_key(#field_name_string)#variant_path)?,
+                }
+            });
+            quote! {
+                if #crate_path::ReadView::kind(view) == #crate_path::ValueKind::Map {
+                    Ok(#ty_name {
+                        #(#fields)*
+                    })
+                } else {
+                    Err(#crate_path::FromAutomergeError::WrongType {
+                        found: #crate_path::ReadView::kind(view),
+                        expected: "a map".to_owned(),
+                    })
+                }
+            }
+        }
+        Fields::Unnamed(u) => {
+            if u.unnamed.len() == 1 {
+                let field_ty = &u.unnamed.first().unwrap().ty;
+                quote! {
+                    <#field_ty as #crate_path::FromView>::from_view(view).map(#ty_name).map_err(|e| e #variant_path)
+                }
+            } else {
+                let fields = u.unnamed.iter().enumerate().map(|(i, f)| {
+                    let view_for_field = quote! {
+                        items.get(#i)
+                    };
+                    let decode = decode_field(&f.attrs, &f.ty, &view_for_field, crate_path);
+                    quote! {
+                        {#decode}.map_err(|e| e.at_index(#i)#variant_path)?,
+                    }
+                });
+                quote! {
+                    if #crate_path::ReadView::kind(view) == #crate_path::ValueKind::List {
+                        let items = #crate_path::ReadView::items(view);
+                        Ok(#ty_name(
+                            #(#fields)*
+                        ))
+                    } else {
+                        Err(#crate_path::FromAutomergeError::WrongType {
+                            found: #crate_path::ReadView::kind(view),
+                            expected: "a list".to_owned(),
+                        })
+                    }
+                }
+            }
+        }
+        Fields::Unit => {
+            quote! {
+                <() as #crate_path::FromView>::from_view(view).map(|()| #ty_name)
+            }
+        }
+    }
+}
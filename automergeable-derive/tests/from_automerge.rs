@@ -1,4 +1,6 @@
-use automergeable::{Automergeable, FromAutomerge, ToAutomerge};
+use automergeable::{
+    ApplyPatch, Automergeable, FromAutomerge, FromAutomergeRef, MapKey, ToAutomerge, TryToAutomerge,
+};
 use insta::{assert_json_snapshot, Settings};
 use pretty_assertions::assert_eq;
 use serde::Serialize;
@@ -439,3 +441,337 @@ fn multi_generics() {
         B::from_automerge(&B::<u32, String>::D(String::new()).to_automerge()).unwrap()
     );
 }
+
+#[test]
+fn out_of_range_integers() {
+    #[derive(ToAutomerge, FromAutomerge, Debug)]
+    struct Small {
+        byte: u8,
+    }
+
+    #[derive(ToAutomerge, FromAutomerge, Debug)]
+    struct Large {
+        byte: u64,
+        big: u128,
+    }
+
+    let large = Large {
+        byte: 300,
+        big: u128::from(u64::MAX) + 1,
+    };
+    assert!(matches!(
+        large.big.try_to_automerge(),
+        Err(automergeable::ToAutomergeError::OutOfRange { .. })
+    ));
+
+    let value = Large { byte: 300, big: 0 }.to_automerge();
+    let err = Small::from_automerge(&value).unwrap_err();
+    match err.cause() {
+        automergeable::FromAutomergeError::OutOfRange { value, target_type } => {
+            assert_eq!(value, "300");
+            assert_eq!(*target_type, "u8");
+        }
+        other => panic!("expected out of range error, got {:?}", other),
+    }
+}
+
+#[test]
+fn fallible_fields() {
+    // only implements `TryToAutomerge`, so neither can anything holding it
+    #[derive(TryToAutomerge, FromAutomerge, ApplyPatch, Debug, PartialEq)]
+    struct Reading {
+        value: f64,
+    }
+
+    #[derive(TryToAutomerge, FromAutomerge, ApplyPatch, Debug, Default, PartialEq)]
+    struct Log {
+        readings: Vec<Reading>,
+        total: u128,
+    }
+
+    let log = Log {
+        readings: vec![Reading { value: 1.5 }],
+        total: 2,
+    };
+    let value = log.try_to_automerge().unwrap();
+    assert_eq!(Log::from_automerge(&value).unwrap(), log);
+
+    let log = Log {
+        readings: vec![Reading { value: f64::NAN }],
+        total: 2,
+    };
+    assert!(matches!(
+        log.try_to_automerge(),
+        Err(automergeable::ToAutomergeError::NotANumber)
+    ));
+
+    let log = Log {
+        readings: Vec::new(),
+        total: u128::MAX,
+    };
+    assert!(matches!(
+        log.try_to_automerge(),
+        Err(automergeable::ToAutomergeError::OutOfRange { .. })
+    ));
+
+    // derived `ToAutomerge` types still report failures through `TryToAutomerge`
+    #[derive(Automergeable, Debug, Default)]
+    struct Total {
+        value: f64,
+    }
+
+    let total = Total { value: f64::NAN };
+    assert!(matches!(
+        total.try_to_automerge(),
+        Err(automergeable::ToAutomergeError::NotANumber)
+    ));
+}
+
+#[test]
+fn errors_report_path_and_kind() {
+    #[derive(ToAutomerge, FromAutomerge, Debug)]
+    struct Doc {
+        tasks: Vec<Task>,
+    }
+
+    #[derive(ToAutomerge, FromAutomerge, Debug, Default)]
+    struct Task {
+        due: i64,
+    }
+
+    #[derive(ToAutomerge, Debug)]
+    struct BadTask {
+        due: String,
+    }
+
+    #[derive(ToAutomerge, Debug)]
+    struct BadDoc {
+        tasks: Vec<BadTask>,
+    }
+
+    let value = BadDoc {
+        tasks: vec![BadTask {
+            due: "tomorrow".to_owned(),
+        }],
+    }
+    .to_automerge();
+    let err = Doc::from_automerge(&value).unwrap_err();
+    assert_eq!(err.path().unwrap().to_string(), ".tasks[0].due");
+    assert!(matches!(
+        err.cause(),
+        automergeable::FromAutomergeError::WrongType {
+            found: automergeable::ValueKind::Str,
+            ..
+        }
+    ));
+    assert_eq!(
+        err.to_string(),
+        "at .tasks[0].due: found a primitive string but expected a primitive int"
+    );
+}
+
+#[test]
+fn timestamps_are_milliseconds() {
+    use std::time::{Duration, SystemTime};
+
+    #[derive(ToAutomerge, FromAutomerge, Debug, PartialEq)]
+    struct Event {
+        at: SystemTime,
+        #[automergeable(representation = "timestamp")]
+        since_epoch: Duration,
+        took: Duration,
+    }
+
+    let event = Event {
+        at: SystemTime::UNIX_EPOCH + Duration::from_millis(1_500),
+        since_epoch: Duration::from_millis(2_500),
+        took: Duration::from_millis(30),
+    };
+    let value = event.to_automerge();
+    if let automerge::Value::Map(map) = &value {
+        assert_eq!(
+            map.get("at"),
+            Some(&automerge::Value::Primitive(
+                automerge::Primitive::Timestamp(1_500)
+            ))
+        );
+        assert_eq!(
+            map.get("since_epoch"),
+            Some(&automerge::Value::Primitive(
+                automerge::Primitive::Timestamp(2_500)
+            ))
+        );
+    } else {
+        panic!("expected a map")
+    }
+    assert_eq!(Event::from_automerge(&value).unwrap(), event);
+
+    let before_epoch = SystemTime::UNIX_EPOCH - Duration::from_millis(10);
+    assert_eq!(
+        SystemTime::from_automerge(&before_epoch.to_automerge()).unwrap(),
+        before_epoch
+    );
+}
+
+#[test]
+fn map_keys() {
+    use std::collections::BTreeSet;
+
+    use automergeable as renamed;
+
+    #[derive(MapKey, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    enum Colour {
+        Red,
+        Green,
+    }
+
+    #[derive(MapKey, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    #[automergeable(crate_path = "renamed")]
+    struct UserId(u32);
+
+    #[derive(Automergeable, Debug, Default, PartialEq)]
+    struct Palette {
+        counts: HashMap<Colour, u64>,
+        owners: HashMap<(UserId, Colour), String>,
+        favourites: BTreeSet<UserId>,
+    }
+
+    let mut palette = Palette::default();
+    palette.counts.insert(Colour::Red, 2);
+    palette
+        .owners
+        .insert((UserId(7), Colour::Green), "seven".to_owned());
+    palette.favourites.insert(UserId(3));
+
+    let value = palette.to_automerge();
+    if let automerge::Value::Map(map) = &value {
+        assert!(
+            matches!(&map["counts"], automerge::Value::Map(counts) if counts.contains_key("Red"))
+        );
+        assert!(
+            matches!(&map["owners"], automerge::Value::Map(owners) if owners.contains_key(r#"["7","Green"]"#))
+        );
+    } else {
+        panic!("expected a map")
+    }
+    assert_eq!(Palette::from_automerge(&value).unwrap(), palette);
+
+    let mut counts = HashMap::new();
+    counts.insert(
+        "Blue".into(),
+        automerge::Value::Primitive(automerge::Primitive::Uint(1)),
+    );
+    let err = HashMap::<Colour, u64>::from_automerge(&automerge::Value::Map(counts)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r#"at .Blue: couldn't parse "Blue" as Colour"#
+    );
+
+    // keys that aren't how the number is displayed would collide with the displayed one
+    for key in &["05", "+5"] {
+        let mut counts = HashMap::new();
+        counts.insert(
+            (*key).into(),
+            automerge::Value::Primitive(automerge::Primitive::Uint(1)),
+        );
+        let err = HashMap::<u32, u64>::from_automerge(&automerge::Value::Map(counts)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(r#"at .{0}: couldn't parse "{0}" as u32"#, key)
+        );
+    }
+}
+
+#[test]
+fn borrowed_fields() {
+    use std::borrow::Cow;
+
+    #[derive(ToAutomerge, Debug, Default)]
+    struct Owned {
+        name: String,
+        data: Vec<u8>,
+        tags: Vec<String>,
+        #[automergeable(representation = "text")]
+        notes: String,
+        count: u64,
+        kind: Kind,
+    }
+
+    #[derive(ToAutomerge, FromAutomergeRef, Debug, PartialEq)]
+    enum Kind {
+        Plain,
+        Named { label: String },
+    }
+
+    impl Default for Kind {
+        fn default() -> Self {
+            Self::Plain
+        }
+    }
+
+    #[derive(FromAutomergeRef, Debug, PartialEq)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        data: Option<&'a [u8]>,
+        tags: Vec<&'a str>,
+        notes: Cow<'a, str>,
+        count: u64,
+        kind: Kind,
+        missing: &'a str,
+    }
+
+    let owned = Owned {
+        name: "hello".to_owned(),
+        data: Vec::new(),
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        notes: "some notes".to_owned(),
+        count: 3,
+        kind: Kind::Named {
+            label: "x".to_owned(),
+        },
+    };
+    let mut value = owned.to_automerge();
+    if let automerge::Value::Map(map) = &mut value {
+        map.insert(
+            "data".into(),
+            automerge::Value::Primitive(automerge::Primitive::Bytes(vec![1, 2])),
+        );
+    }
+
+    let borrowed = Borrowed::from_automerge_ref(&value).unwrap();
+    assert_eq!(
+        borrowed,
+        Borrowed {
+            name: "hello",
+            data: Some(&[1, 2]),
+            tags: vec!["a", "b"],
+            notes: Cow::Owned("some notes".to_owned()),
+            count: 3,
+            kind: Kind::Named {
+                label: "x".to_owned()
+            },
+            missing: "",
+        }
+    );
+    if let automerge::Value::Map(map) = &value {
+        if let automerge::Value::Primitive(automerge::Primitive::Str(name)) = &map["name"] {
+            assert!(std::ptr::eq(borrowed.name, name.as_str()));
+        }
+    }
+
+    let err = Borrowed::from_automerge_ref(&automerge::Value::Map(
+        vec![(
+            "tags".into(),
+            automerge::Value::List(vec![automerge::Value::Primitive(
+                automerge::Primitive::Uint(1),
+            )]),
+        )]
+        .into_iter()
+        .collect(),
+    ))
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "at .tags[0]: found a primitive uint but expected a primitive string"
+    );
+}
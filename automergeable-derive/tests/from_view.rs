@@ -0,0 +1,99 @@
+use automerge::{transaction::Transactable, Automerge, ObjType, ROOT};
+use automergeable::{Automergeable, FromView, Text};
+use automergeable_view::{View, ViewableDoc};
+use pretty_assertions::assert_eq;
+
+#[derive(FromView, Debug, Default, PartialEq)]
+struct Task {
+    title: Text,
+    done: bool,
+    tags: Vec<String>,
+    status: Status,
+    note: Option<String>,
+}
+
+#[derive(FromView, Debug, PartialEq)]
+enum Status {
+    Todo,
+    Blocked { reason: String },
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::Todo
+    }
+}
+
+#[test]
+fn from_view() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let title = tx.put_object(&ROOT, "title", ObjType::Text).unwrap();
+    tx.insert(&title, 0, "h").unwrap();
+    tx.insert(&title, 1, "i").unwrap();
+    tx.put(&ROOT, "done", true).unwrap();
+    let tags = tx.put_object(&ROOT, "tags", ObjType::List).unwrap();
+    tx.insert(&tags, 0, "a").unwrap();
+    let status = tx.put_object(&ROOT, "status", ObjType::Map).unwrap();
+    let blocked = tx.put_object(&status, "Blocked", ObjType::Map).unwrap();
+    tx.put(&blocked, "reason", "waiting").unwrap();
+    tx.commit();
+
+    assert_eq!(
+        Task::from_view(&View::Map(doc.view())).unwrap(),
+        Task {
+            title: Text::from("hi"),
+            done: true,
+            tags: vec!["a".to_owned()],
+            status: Status::Blocked {
+                reason: "waiting".to_owned()
+            },
+            note: None,
+        }
+    );
+
+    // unit variants are strings and missing fields are defaulted
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(&ROOT, "status", "Todo").unwrap();
+    tx.commit();
+    assert_eq!(
+        Task::from_view(&View::Map(doc.view())).unwrap(),
+        Task::default()
+    );
+
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    tx.put(&ROOT, "done", "yes").unwrap();
+    tx.commit();
+    assert_eq!(
+        Task::from_view(&View::Map(doc.view()))
+            .unwrap_err()
+            .to_string(),
+        "at .done: found a primitive string but expected a primitive boolean"
+    );
+}
+
+#[test]
+fn automergeable_derives_from_view() {
+    #[derive(Automergeable, Debug, Default, PartialEq)]
+    struct Note {
+        body: Text,
+        likes: u64,
+    }
+
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let body = tx.put_object(&ROOT, "body", ObjType::Text).unwrap();
+    tx.insert(&body, 0, "a").unwrap();
+    tx.put(&ROOT, "likes", 2_u64).unwrap();
+    tx.commit();
+
+    assert_eq!(
+        Note::from_view(&View::Map(doc.view())).unwrap(),
+        Note {
+            body: Text::from("a"),
+            likes: 2,
+        }
+    );
+}
@@ -0,0 +1,117 @@
+use automerge::{transaction::Transactable, Automerge};
+use automergeable::{FromView, Reconcile, Text};
+use automergeable_view::{Fields, MutableDoc, MutableView, View, ViewableDoc};
+use pretty_assertions::assert_eq;
+
+#[derive(Reconcile, FromView, Debug, Default, Clone, PartialEq)]
+struct Task {
+    title: Text,
+    tags: Vec<String>,
+    status: Status,
+    #[automergeable(representation = "counter")]
+    likes: i64,
+    #[automergeable(representation = "timestamp")]
+    due: i64,
+}
+
+#[derive(Reconcile, FromView, Debug, Clone, PartialEq)]
+enum Status {
+    Todo,
+    Blocked { reason: String },
+    Done(u64),
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Self::Todo
+    }
+}
+
+/// Reconcile `task` into the document, giving the number of operations it took.
+fn reconcile(doc: &mut Automerge, task: &Task) -> usize {
+    let mut tx = doc.transaction();
+    task.reconcile(&mut MutableView::Map(tx.view_mut()))
+        .unwrap();
+    let ops = tx.pending_ops();
+    tx.commit();
+    assert_eq!(&Task::from_view(&View::Map(doc.view())).unwrap(), task);
+    ops
+}
+
+#[test]
+fn reconcile_struct() {
+    let mut doc = Automerge::new();
+    let mut task = Task {
+        title: Text::from("write"),
+        tags: vec!["a".to_owned(), "b".to_owned()],
+        ..Task::default()
+    };
+    reconcile(&mut doc, &task);
+
+    // nothing changed so nothing is written
+    assert_eq!(reconcile(&mut doc, &task), 0);
+
+    // a splice, an insert at the front of the list and an increment
+    task.title.push_str("s");
+    task.tags.insert(0, "z".to_owned());
+    task.likes += 2;
+    assert_eq!(reconcile(&mut doc, &task), 3);
+
+    task.due = 1_000;
+    assert_eq!(reconcile(&mut doc, &task), 1);
+}
+
+#[test]
+fn reconcile_enum() {
+    let mut doc = Automerge::new();
+    let mut task = Task::default();
+    reconcile(&mut doc, &task);
+
+    task.status = Status::Blocked {
+        reason: "waiting".to_owned(),
+    };
+    reconcile(&mut doc, &task);
+    assert_eq!(reconcile(&mut doc, &task), 0);
+
+    // the same variant is updated in place
+    task.status = Status::Blocked {
+        reason: "busy".to_owned(),
+    };
+    assert_eq!(reconcile(&mut doc, &task), 1);
+
+    task.status = Status::Done(3);
+    reconcile(&mut doc, &task);
+
+    task.status = Status::Todo;
+    reconcile(&mut doc, &task);
+}
+
+#[test]
+fn matching_items_are_skipped() {
+    let mut doc = Automerge::new();
+    let mut tasks = vec![
+        Task {
+            title: Text::from("a"),
+            ..Task::default()
+        },
+        Task {
+            title: Text::from("b"),
+            status: Status::Done(1),
+            ..Task::default()
+        },
+    ];
+    let mut tx = doc.transaction();
+    Fields(&[("tasks", &tasks as &dyn Reconcile)])
+        .reconcile(&mut MutableView::Map(tx.view_mut()))
+        .unwrap();
+    tx.commit();
+
+    // removing the first task leaves the second alone rather than rewriting it into the first
+    tasks.remove(0);
+    let mut tx = doc.transaction();
+    Fields(&[("tasks", &tasks as &dyn Reconcile)])
+        .reconcile(&mut MutableView::Map(tx.view_mut()))
+        .unwrap();
+    assert_eq!(tx.pending_ops(), 1);
+    tx.commit();
+}
@@ -0,0 +1,86 @@
+use std::{
+    fmt,
+    ops::{AddAssign, SubAssign},
+};
+
+use automerge::{Primitive, Value};
+
+use crate::{ApplyPatch, FromAutomerge, FromAutomergeError, ToAutomerge, ValueKind};
+
+/// An automerge counter, which merges concurrent changes by adding them together.
+///
+/// A counter can only be incremented and decremented once it is stored in a document. Replacing
+/// it with another counter is stored as the increment between the two values, so there is no
+/// way to reset it that concurrent increments won't add to.
+///
+/// ```rust
+/// # use automergeable_traits::Counter;
+/// let mut likes = Counter::new(1);
+/// likes.increment(2);
+/// likes.decrement(1);
+/// likes += 3;
+/// assert_eq!(likes.value(), 5);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Counter(i64);
+
+impl Counter {
+    /// Create a counter with an initial value.
+    pub const fn new(value: i64) -> Self {
+        Self(value)
+    }
+
+    /// The current value of the counter.
+    pub const fn value(&self) -> i64 {
+        self.0
+    }
+
+    /// Add `n` to the counter.
+    pub fn increment(&mut self, n: i64) {
+        self.0 += n
+    }
+
+    /// Subtract `n` from the counter.
+    pub fn decrement(&mut self, n: i64) {
+        self.0 -= n
+    }
+}
+
+impl AddAssign<i64> for Counter {
+    fn add_assign(&mut self, n: i64) {
+        self.increment(n)
+    }
+}
+
+impl SubAssign<i64> for Counter {
+    fn sub_assign(&mut self, n: i64) {
+        self.decrement(n)
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ToAutomerge for Counter {
+    fn to_automerge(&self) -> Value {
+        Value::Primitive(Primitive::Counter(self.0))
+    }
+}
+
+impl FromAutomerge for Counter {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Primitive(Primitive::Counter(i)) = value {
+            Ok(Self(*i))
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a primitive counter".to_owned(),
+            })
+        }
+    }
+}
+
+impl ApplyPatch for Counter {}
@@ -0,0 +1,519 @@
+//! Conversions for types from other crates, each behind a feature of the same name.
+
+#[cfg(any(feature = "indexmap", feature = "im"))]
+use std::hash::Hash;
+#[cfg(any(
+    feature = "uuid",
+    feature = "url",
+    feature = "rust_decimal",
+    feature = "semver"
+))]
+use std::str::FromStr;
+
+#[cfg(any(
+    feature = "uuid",
+    feature = "url",
+    feature = "rust_decimal",
+    feature = "semver",
+    feature = "bytes"
+))]
+use automerge::Primitive;
+#[cfg(any(
+    feature = "uuid",
+    feature = "url",
+    feature = "rust_decimal",
+    feature = "semver",
+    feature = "bytes",
+    feature = "indexmap",
+    feature = "ordered-float",
+    feature = "im"
+))]
+use automerge::Value;
+#[cfg(feature = "im")]
+use automerge_protocol::Diff;
+#[cfg(any(
+    feature = "uuid",
+    feature = "url",
+    feature = "rust_decimal",
+    feature = "semver"
+))]
+use smol_str::SmolStr;
+
+#[cfg(any(feature = "indexmap", feature = "im"))]
+use crate::to::expect_value;
+#[cfg(any(feature = "uuid", feature = "im"))]
+use crate::MapKey;
+#[cfg(any(feature = "indexmap", feature = "ordered-float", feature = "im"))]
+use crate::ToAutomergeError;
+#[cfg(any(feature = "bytes", feature = "im"))]
+use crate::ValueKind;
+#[cfg(feature = "im")]
+use crate::{
+    set::{set_changes, set_from_automerge, set_to_automerge},
+    RootAutomergeable,
+};
+#[cfg(any(
+    feature = "uuid",
+    feature = "url",
+    feature = "rust_decimal",
+    feature = "semver",
+    feature = "bytes",
+    feature = "indexmap",
+    feature = "ordered-float",
+    feature = "im"
+))]
+use crate::{ApplyPatch, FromAutomerge, FromAutomergeError, ToAutomerge};
+
+/// Store types as a string in their canonical textual format, parsing them on the way out.
+macro_rules! string_automerge {
+    ( $( $(#[$attr:meta])* $x:ty ),* $(,)? ) => {
+        $(
+        $(#[$attr])*
+        impl ToAutomerge for $x {
+            fn to_automerge(&self) -> Value {
+                Value::Primitive(Primitive::Str(SmolStr::new(self.to_string())))
+            }
+        }
+
+        $(#[$attr])*
+        impl FromAutomerge for $x {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                let s = String::from_automerge(value)?;
+                <$x>::from_str(&s).map_err(|_| FromAutomergeError::Parse {
+                    value: s,
+                    target_type: stringify!($x),
+                })
+            }
+        }
+
+        $(#[$attr])*
+        impl ApplyPatch for $x {}
+        )*
+    };
+}
+
+string_automerge! {
+    #[cfg(feature = "uuid")]
+    uuid::Uuid,
+    #[cfg(feature = "url")]
+    url::Url,
+    // decimals are stored as strings as f64s can't represent them exactly
+    #[cfg(feature = "rust_decimal")]
+    rust_decimal::Decimal,
+    #[cfg(feature = "semver")]
+    semver::Version,
+}
+
+/// UUIDs are keyed by their lowercase hyphenated form.
+#[cfg(feature = "uuid")]
+impl MapKey for uuid::Uuid {
+    fn to_key(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+        Self::from_str(key).map_err(|_| FromAutomergeError::Parse {
+            value: key.to_owned(),
+            target_type: "uuid::Uuid",
+        })
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ToAutomerge for bytes::Bytes {
+    fn to_automerge(&self) -> Value {
+        Value::Primitive(Primitive::Bytes(self.to_vec()))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl FromAutomerge for bytes::Bytes {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Primitive(Primitive::Bytes(b)) = value {
+            Ok(Self::copy_from_slice(b))
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "primitive bytes".to_owned(),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ApplyPatch for bytes::Bytes {}
+
+/// Index maps are stored as a list of `[key, value]` pairs to keep their order.
+///
+/// Should the same key end up in the list twice, such as after concurrent inserts, the position
+/// of the first is kept with the value of the last, as with [`IndexMap::insert`].
+///
+/// [`IndexMap::insert`]: indexmap::IndexMap::insert
+#[cfg(feature = "indexmap")]
+impl<K, V> ToAutomerge for indexmap::IndexMap<K, V>
+where
+    K: ToAutomerge,
+    V: ToAutomerge,
+{
+    fn to_automerge(&self) -> Value {
+        expect_value(self.to_automerge_checked())
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let entries = self
+            .iter()
+            .map(|(k, v)| {
+                Ok(Value::List(vec![
+                    k.to_automerge_checked()?,
+                    v.to_automerge_checked()?,
+                ]))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(entries))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V> FromAutomerge for indexmap::IndexMap<K, V>
+where
+    K: FromAutomerge + Hash + Eq,
+    V: FromAutomerge,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        let entries = Vec::<(K, V)>::from_automerge(value)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V> ApplyPatch for indexmap::IndexMap<K, V>
+where
+    K: FromAutomerge + Hash + Eq,
+    V: FromAutomerge,
+{
+}
+
+#[cfg(feature = "ordered-float")]
+impl<T> ToAutomerge for ordered_float::OrderedFloat<T>
+where
+    T: ToAutomerge,
+{
+    fn to_automerge(&self) -> Value {
+        self.0.to_automerge()
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        self.0.to_automerge_checked()
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl<T> FromAutomerge for ordered_float::OrderedFloat<T>
+where
+    T: FromAutomerge,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        T::from_automerge(value).map(Self)
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+impl<T> ApplyPatch for ordered_float::OrderedFloat<T> where T: FromAutomerge {}
+
+#[cfg(feature = "ordered-float")]
+macro_rules! not_nan_automerge {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl ToAutomerge for ordered_float::NotNan<$x> {
+            fn to_automerge(&self) -> Value {
+                self.into_inner().to_automerge()
+            }
+        }
+
+        impl FromAutomerge for ordered_float::NotNan<$x> {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                let f = <$x>::from_automerge(value)?;
+                Self::new(f).map_err(|_| FromAutomergeError::OutOfRange {
+                    value: f.to_string(),
+                    target_type: concat!("NotNan<", stringify!($x), ">"),
+                })
+            }
+        }
+
+        impl ApplyPatch for ordered_float::NotNan<$x> {})*
+    };
+}
+
+#[cfg(feature = "ordered-float")]
+not_nan_automerge! {
+    f32,
+    f64,
+}
+
+#[cfg(feature = "im")]
+impl<T> ToAutomerge for im::Vector<T>
+where
+    T: ToAutomerge + Clone,
+{
+    fn to_automerge(&self) -> Value {
+        let vals = self.iter().map(|v| v.to_automerge()).collect::<Vec<_>>();
+        Value::List(vals)
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let vals = self
+            .iter()
+            .map(|v| v.to_automerge_checked())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(vals))
+    }
+}
+
+#[cfg(feature = "im")]
+impl<T> FromAutomerge for im::Vector<T>
+where
+    T: FromAutomerge + Clone,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        Vec::from_automerge(value).map(Self::from)
+    }
+}
+
+#[cfg(feature = "im")]
+impl<T> ApplyPatch for im::Vector<T> where T: FromAutomerge + Clone {}
+
+/// Persistent maps are stored like the std maps, keyed by the [`MapKey`] of each key.
+#[cfg(feature = "im")]
+macro_rules! im_map_automerge {
+    ( $( $x:ident: $( $bound:path ),+ );* $(;)? ) => {
+        $(
+        impl<K, V> ToAutomerge for im::$x<K, V>
+        where
+            K: MapKey $( + $bound )+,
+            V: ToAutomerge + Clone,
+        {
+            fn to_automerge(&self) -> Value {
+                expect_value(self.to_automerge_checked())
+            }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                let mut hm = std::collections::HashMap::with_capacity(self.len());
+                for (k, v) in self {
+                    hm.insert(k.to_key().into(), v.to_automerge_checked()?);
+                }
+                Ok(Value::Map(hm))
+            }
+        }
+
+        impl<K, V> FromAutomerge for im::$x<K, V>
+        where
+            K: MapKey $( + $bound )+,
+            V: FromAutomerge + Clone,
+        {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                if let Value::Map(map) = value {
+                    let mut m = Self::new();
+                    for (k, v) in map {
+                        let key = K::from_key(k).map_err(|e| e.at_key(k))?;
+                        m.insert(key, V::from_automerge(v).map_err(|e| e.at_key(k))?);
+                    }
+                    Ok(m)
+                } else {
+                    Err(FromAutomergeError::WrongType {
+                        found: ValueKind::from(value),
+                        expected: "a map".to_owned(),
+                    })
+                }
+            }
+        }
+
+        impl<K, V> ApplyPatch for im::$x<K, V>
+        where
+            K: MapKey $( + $bound )+,
+            V: FromAutomerge + Clone,
+        {
+        }
+
+        impl<K, V> RootAutomergeable for im::$x<K, V> where Self: crate::Automergeable {})*
+    };
+}
+
+#[cfg(feature = "im")]
+im_map_automerge! {
+    HashMap: Hash, Eq, Clone;
+    OrdMap: Ord, Clone;
+}
+
+/// Persistent sets are stored like the std sets, as a map from the [`MapKey`] of each element to `true`.
+#[cfg(feature = "im")]
+macro_rules! im_set_automerge {
+    ( $( $x:ident: $( $bound:path ),+ );* $(;)? ) => {
+        $(
+        impl<T> ToAutomerge for im::$x<T>
+        where
+            T: MapKey $( + $bound )+,
+        {
+            fn to_automerge(&self) -> Value {
+                set_to_automerge(self.iter())
+            }
+        }
+
+        impl<T> FromAutomerge for im::$x<T>
+        where
+            T: MapKey $( + $bound )+,
+        {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                set_from_automerge(value)
+            }
+        }
+
+        impl<T> ApplyPatch for im::$x<T>
+        where
+            T: MapKey $( + $bound )+,
+        {
+            fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+                if let Some(changes) = set_changes(diff, value)? {
+                    for (element, present) in changes {
+                        if present {
+                            self.insert(element);
+                        } else {
+                            self.remove(&element);
+                        }
+                    }
+                } else {
+                    *self = Self::from_automerge(value)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl<T> RootAutomergeable for im::$x<T> where Self: crate::Automergeable {})*
+    };
+}
+
+#[cfg(feature = "im")]
+im_set_automerge! {
+    HashSet: Hash, Eq, Clone;
+    OrdSet: Ord, Clone;
+}
+
+#[cfg(all(
+    test,
+    any(
+        feature = "uuid",
+        feature = "url",
+        feature = "rust_decimal",
+        feature = "semver",
+        feature = "bytes",
+        feature = "indexmap",
+        feature = "ordered-float",
+        feature = "im"
+    )
+))]
+mod tests {
+    use std::fmt::Debug;
+
+    use automerge::Value;
+
+    use crate::{FromAutomerge, ToAutomerge};
+
+    fn roundtrip<T>(value: T) -> Value
+    where
+        T: ToAutomerge + FromAutomerge + PartialEq + Debug,
+    {
+        let automerge = value.to_automerge();
+        assert_eq!(T::from_automerge(&automerge).unwrap(), value);
+        automerge
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        use automerge::Primitive;
+
+        let id = uuid::Uuid::from_u128(0x6ba7_b810_9dad_11d1_80b4_00c0_4fd4_30c8);
+        assert_eq!(
+            roundtrip(id),
+            Value::Primitive(Primitive::Str(
+                "6ba7b810-9dad-11d1-80b4-00c04fd430c8".into()
+            ))
+        );
+
+        let mut map = std::collections::HashMap::new();
+        map.insert(id, 1_i64);
+        roundtrip(map);
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn url() {
+        roundtrip(url::Url::parse("https://example.com/a?b=c#d").unwrap());
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn rust_decimal() {
+        use std::str::FromStr;
+
+        use automerge::Primitive;
+
+        let decimal = rust_decimal::Decimal::from_str("0.10").unwrap();
+        assert_eq!(
+            roundtrip(decimal),
+            Value::Primitive(Primitive::Str("0.10".into()))
+        );
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn semver() {
+        roundtrip(semver::Version::parse("1.2.3-alpha.1+build").unwrap());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes() {
+        use automerge::Primitive;
+
+        assert_eq!(
+            roundtrip(bytes::Bytes::from_static(b"abc")),
+            Value::Primitive(Primitive::Bytes(b"abc".to_vec()))
+        );
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn indexmap() {
+        let mut map = indexmap::IndexMap::new();
+        map.insert("b".to_owned(), 2_i64);
+        map.insert("a".to_owned(), 1_i64);
+        let value = roundtrip(map.clone());
+        let decoded = indexmap::IndexMap::<String, i64>::from_automerge(&value).unwrap();
+        assert!(decoded.keys().eq(map.keys()));
+    }
+
+    #[cfg(feature = "ordered-float")]
+    #[test]
+    fn ordered_float() {
+        use automerge::Primitive;
+
+        roundtrip(ordered_float::OrderedFloat(1.5_f64));
+        roundtrip(ordered_float::NotNan::new(2.5_f32).unwrap());
+        assert!(
+            ordered_float::NotNan::<f64>::from_automerge(&Value::Primitive(Primitive::F64(
+                f64::NAN
+            )))
+            .is_err()
+        );
+    }
+
+    #[cfg(feature = "im")]
+    #[test]
+    fn im() {
+        roundtrip(im::vector![1_i64, 2, 3]);
+        roundtrip(im::hashmap! {"a".to_owned() => 1_i64});
+        roundtrip(im::ordmap! {2_i64 => "b".to_owned()});
+        roundtrip(im::hashset! {"a".to_owned()});
+        roundtrip(im::ordset! {1_i64, 2});
+    }
+}
@@ -1,10 +1,14 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    convert::TryInto,
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::{TryFrom, TryInto},
     error::Error,
+    fmt,
     hash::Hash,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    path::PathBuf,
     rc::Rc,
-    str::FromStr,
     sync::Arc,
 };
 
@@ -12,6 +16,8 @@ use automerge::{Primitive, Value};
 use serde_json::Number;
 use smol_str::SmolStr;
 
+use crate::MapKey;
+
 /// Require a method to convert to a value from an automerge value.
 pub trait FromAutomerge: Sized {
     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError>;
@@ -20,15 +26,176 @@ pub trait FromAutomerge: Sized {
 /// A failure converting from an automerge value to Rust type.
 #[derive(thiserror::Error, Debug)]
 pub enum FromAutomergeError {
-    #[error("found the wrong type")]
-    WrongType {
-        found: automerge::Value,
-        expected: String,
-    },
+    #[error("found {found} but expected {expected}")]
+    WrongType { found: ValueKind, expected: String },
     #[error("failed converting from automerge")]
     FailedTryFrom,
+    #[error("{value} is out of range for {target_type}")]
+    OutOfRange {
+        /// The value that was found.
+        value: String,
+        /// The type it couldn't be converted into.
+        target_type: &'static str,
+    },
+    /// A string wasn't in the format of the type it is parsed into.
+    #[error("couldn't parse {value:?} as {target_type}")]
+    Parse {
+        /// The string that was found.
+        value: String,
+        /// The type it couldn't be parsed into.
+        target_type: &'static str,
+    },
     #[error("unknown error: {0}")]
     Unknown(#[from] Box<dyn Error + Send + Sync>),
+    /// An error decoding a value nested inside the one being converted.
+    #[error("at {path}: {error}")]
+    AtPath {
+        /// Where the error happened, relative to the value being converted.
+        path: ErrorPath,
+        /// The error at that location.
+        error: Box<FromAutomergeError>,
+    },
+}
+
+impl FromAutomergeError {
+    /// Record that this error happened within the map entry for `key`.
+    #[must_use]
+    pub fn at_key(self, key: &str) -> Self {
+        self.at(PathSegment::Key(key.to_owned()))
+    }
+
+    /// Record that this error happened within the list element at `index`.
+    #[must_use]
+    pub fn at_index(self, index: usize) -> Self {
+        self.at(PathSegment::Index(index))
+    }
+
+    fn at(self, segment: PathSegment) -> Self {
+        match self {
+            Self::AtPath { mut path, error } => {
+                // we are unwinding from the failure so each segment is the parent of the last
+                path.0.insert(0, segment);
+                Self::AtPath { path, error }
+            }
+            error => Self::AtPath {
+                path: ErrorPath(vec![segment]),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// The location of the error, if it happened inside the value being converted.
+    pub const fn path(&self) -> Option<&ErrorPath> {
+        if let Self::AtPath { path, .. } = self {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// The underlying error, without the location.
+    pub fn cause(&self) -> &Self {
+        if let Self::AtPath { error, .. } = self {
+            error
+        } else {
+            self
+        }
+    }
+}
+
+/// The location of an error within a value, such as `.tasks[3].due`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorPath(Vec<PathSegment>);
+
+impl ErrorPath {
+    /// The segments of the path, from the outermost value inwards.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ErrorPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single step into a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// An entry in a map.
+    Key(String),
+    /// An element of a list.
+    Index(usize),
+}
+
+/// The kind of an automerge value, used to report what was found without holding the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    Map,
+    Table,
+    List,
+    Text,
+    Bytes,
+    Str,
+    Int,
+    Uint,
+    F64,
+    Counter,
+    Timestamp,
+    Boolean,
+    Cursor,
+    Null,
+}
+
+impl From<&Value> for ValueKind {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Map(_) => Self::Map,
+            Value::Table(_) => Self::Table,
+            Value::List(_) => Self::List,
+            Value::Text(_) => Self::Text,
+            Value::Primitive(p) => match p {
+                Primitive::Bytes(_) => Self::Bytes,
+                Primitive::Str(_) => Self::Str,
+                Primitive::Int(_) => Self::Int,
+                Primitive::Uint(_) => Self::Uint,
+                Primitive::F64(_) => Self::F64,
+                Primitive::Counter(_) => Self::Counter,
+                Primitive::Timestamp(_) => Self::Timestamp,
+                Primitive::Boolean(_) => Self::Boolean,
+                Primitive::Cursor(_) => Self::Cursor,
+                Primitive::Null => Self::Null,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Map => "a map",
+            Self::Table => "a table",
+            Self::List => "a list",
+            Self::Text => "some text",
+            Self::Bytes => "primitive bytes",
+            Self::Str => "a primitive string",
+            Self::Int => "a primitive int",
+            Self::Uint => "a primitive uint",
+            Self::F64 => "a primitive f64",
+            Self::Counter => "a primitive counter",
+            Self::Timestamp => "a primitive timestamp",
+            Self::Boolean => "a primitive boolean",
+            Self::Cursor => "a cursor",
+            Self::Null => "a primitive null",
+        })
+    }
 }
 
 impl FromAutomerge for Value {
@@ -43,7 +210,7 @@ impl FromAutomerge for () {
             Ok(())
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive null".to_owned(),
             })
         }
@@ -56,13 +223,70 @@ impl FromAutomerge for String {
             Ok(s.to_string())
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive string".to_owned(),
             })
         }
     }
 }
 
+macro_rules! str_from_automerge {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl FromAutomerge for $x {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                String::from_automerge(value).map(Self::from)
+            }
+        })*
+    };
+}
+
+str_from_automerge! {
+    Box<str>,
+    Rc<str>,
+    Arc<str>,
+    PathBuf,
+}
+
+macro_rules! parse_from_automerge {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl FromAutomerge for $x {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                let s = String::from_automerge(value)?;
+                s.parse().map_err(|_| FromAutomergeError::Parse {
+                    value: s,
+                    target_type: stringify!($x),
+                })
+            }
+        })*
+    };
+}
+
+parse_from_automerge! {
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+}
+
+impl FromAutomerge for Ordering {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        let s = String::from_automerge(value)?;
+        match s.as_str() {
+            "Less" => Ok(Self::Less),
+            "Equal" => Ok(Self::Equal),
+            "Greater" => Ok(Self::Greater),
+            _ => Err(FromAutomergeError::Parse {
+                value: s,
+                target_type: "std::cmp::Ordering",
+            }),
+        }
+    }
+}
+
 impl FromAutomerge for char {
     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
         if let Value::Primitive(Primitive::Str(s)) = value {
@@ -70,13 +294,13 @@ impl FromAutomerge for char {
                 Ok(s.chars().next().unwrap())
             } else {
                 Err(FromAutomergeError::WrongType {
-                    found: value.clone(),
+                    found: ValueKind::from(value),
                     expected: "a primitive string".to_owned(),
                 })
             }
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive string".to_owned(),
             })
         }
@@ -90,74 +314,94 @@ where
     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
         if let Value::List(vec) = value {
             let mut v = Self::with_capacity(vec.len());
-            for val in vec {
-                v.push(T::from_automerge(val)?)
+            for (i, val) in vec.iter().enumerate() {
+                v.push(T::from_automerge(val).map_err(|e| e.at_index(i))?)
             }
             Ok(v)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a list".to_owned(),
             })
         }
     }
 }
 
-/// A new-type struct for working with the automerge Text value type.
-pub struct Text(pub Vec<SmolStr>);
+impl<T> FromAutomerge for VecDeque<T>
+where
+    T: FromAutomerge,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        Vec::from_automerge(value).map(Self::from)
+    }
+}
 
-impl FromAutomerge for Text {
+impl<T, const N: usize> FromAutomerge for [T; N]
+where
+    T: FromAutomerge,
+{
     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        if let Value::Text(vec) = value {
-            Ok(Self(vec.clone()))
-        } else {
-            Err(FromAutomergeError::WrongType {
-                found: value.clone(),
-                expected: "some text".to_owned(),
-            })
-        }
+        let vec = Vec::from_automerge(value)?;
+        <[T; N]>::try_from(vec).map_err(|_| FromAutomergeError::WrongType {
+            found: ValueKind::from(value),
+            expected: format!("a list of {} elements", N),
+        })
     }
 }
 
-// impl<T> FromAutomerge for HashSet<T>
-// where
-//     T: FromAutomerge + Clone + Eq + Hash,
-// {
-//     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-//         if let Value::List(vec) = value {
-//             let mut v = Vec::with_capacity(vec.len());
-//             for val in vec {
-//                 v.push(T::from_automerge(val)?)
-//             }
-//             Ok(v.iter().cloned().collect::<HashSet<_>>())
-//         } else {
-//             Err(FromAutomergeError::WrongType {
-//                 found: value.clone(),
-//                 expected: "a list".to_owned(),
-//             })
-//         }
-//     }
-// }
+macro_rules! tuple_from_automerge {
+    ( $( $len:literal => ( $( $t:ident $i:tt ),+ ) ),* $(,)? ) => {
+        $(
+        impl<$( $t ),+> FromAutomerge for ( $( $t, )+ )
+        where
+            $( $t: FromAutomerge, )+
+        {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                match value {
+                    Value::List(vals) if vals.len() == $len => Ok(($(
+                        $t::from_automerge(&vals[$i]).map_err(|e| e.at_index($i))?,
+                    )+)),
+                    _ => Err(FromAutomergeError::WrongType {
+                        found: ValueKind::from(value),
+                        expected: format!("a list of {} elements", $len),
+                    }),
+                }
+            }
+        })*
+    };
+}
+
+tuple_from_automerge! {
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+    7 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    8 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    9 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    10 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    11 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    12 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11),
+}
 
 impl<K, V> FromAutomerge for HashMap<K, V>
 where
-    K: FromStr + Eq + Hash,
+    K: MapKey + Eq + Hash,
     V: FromAutomerge,
 {
     fn from_automerge(value: &automerge::Value) -> std::result::Result<Self, FromAutomergeError> {
         if let Value::Map(map) = value {
             let mut m = Self::with_capacity(map.len());
             for (k, v) in map {
-                if let Ok(k) = K::from_str(k) {
-                    m.insert(k, V::from_automerge(v)?);
-                } else {
-                    return Err(FromAutomergeError::FailedTryFrom);
-                }
+                let key = K::from_key(k).map_err(|e| e.at_key(k))?;
+                m.insert(key, V::from_automerge(v).map_err(|e| e.at_key(k))?);
             }
             Ok(m)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a map".to_owned(),
             })
         }
@@ -166,23 +410,20 @@ where
 
 impl<K, V> FromAutomerge for BTreeMap<K, V>
 where
-    K: FromStr + Eq + Ord,
+    K: MapKey + Eq + Ord,
     V: FromAutomerge,
 {
     fn from_automerge(value: &automerge::Value) -> std::result::Result<Self, FromAutomergeError> {
         if let Value::Map(map) = value {
             let mut m = Self::new();
             for (k, v) in map {
-                if let Ok(k) = K::from_str(k) {
-                    m.insert(k, V::from_automerge(v)?);
-                } else {
-                    return Err(FromAutomergeError::FailedTryFrom);
-                }
+                let key = K::from_key(k).map_err(|e| e.at_key(k))?;
+                m.insert(key, V::from_automerge(v).map_err(|e| e.at_key(k))?);
             }
             Ok(m)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a map".to_owned(),
             })
         }
@@ -202,17 +443,23 @@ where
     }
 }
 
-#[cfg(feature = "std")]
-impl FromAutomerge for std::time::SystemTime {
-    fn from_automerge(value: &automerge::Value) -> Result<Self, FromAutomergeError> {
-        if let Value::Primitive(Primitive::Timestamp(t)) = value {
-            let duration = std::time::Duration::from_secs((*t).try_into().unwrap());
-            Ok(Self::UNIX_EPOCH + duration)
-        } else {
-            Err(FromAutomergeError::WrongType {
-                found: value.clone(),
-                expected: "a primitive timestamp".to_owned(),
-            })
+impl<T, E> FromAutomerge for Result<T, E>
+where
+    T: FromAutomerge,
+    E: FromAutomerge,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        let entry = match value {
+            Value::Map(map) if map.len() == 1 => map.iter().next(),
+            _ => None,
+        };
+        match entry {
+            Some((k, v)) if k == "Ok" => T::from_automerge(v).map(Ok).map_err(|e| e.at_key(k)),
+            Some((k, v)) if k == "Err" => E::from_automerge(v).map(Err).map_err(|e| e.at_key(k)),
+            _ => Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a map with an Ok or Err key".to_owned(),
+            }),
         }
     }
 }
@@ -223,7 +470,7 @@ impl FromAutomerge for bool {
             Ok(*b)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive boolean".to_owned(),
             })
         }
@@ -236,38 +483,30 @@ impl FromAutomerge for i64 {
             Ok(*i)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive int".to_owned(),
             })
         }
     }
 }
 
-macro_rules! as_i64_from_automerge {
-    ( $( $x:ty ),* $(,)? ) => {
+macro_rules! checked_from_automerge {
+    ( $from:ty => $( $x:ty ),* $(,)? ) => {
         $(
         impl FromAutomerge for $x {
             fn from_automerge(value: &automerge::Value) -> Result<Self, FromAutomergeError>{
-                i64::from_automerge(value).map(|i| i as $x)
+                let n = <$from>::from_automerge(value)?;
+                n.try_into().map_err(|_| FromAutomergeError::OutOfRange {
+                    value: n.to_string(),
+                    target_type: stringify!($x),
+                })
             }
         })*
     };
 }
 
-as_i64_from_automerge! {
-    i8,i16,i32
-}
-
-impl FromAutomerge for isize {
-    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        i64::from_automerge(value).map(|i| i as Self)
-    }
-}
-
-impl FromAutomerge for i128 {
-    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        i64::from_automerge(value).map(|i| i as Self)
-    }
+checked_from_automerge! {
+    i64 => i8, i16, i32, isize, i128
 }
 
 impl FromAutomerge for u64 {
@@ -276,38 +515,15 @@ impl FromAutomerge for u64 {
             Ok(*u)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive uint".to_owned(),
             })
         }
     }
 }
 
-macro_rules! as_u64_from_automerge {
-    ( $( $x:ty ),* $(,)? ) => {
-        $(
-        impl FromAutomerge for $x {
-            fn from_automerge(value: &automerge::Value) -> Result<Self, FromAutomergeError>{
-                u64::from_automerge(value).map(|u| u as $x)
-            }
-        })*
-    };
-}
-
-as_u64_from_automerge! {
-    u8,u16,u32
-}
-
-impl FromAutomerge for usize {
-    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        u64::from_automerge(value).map(|u| u as Self)
-    }
-}
-
-impl FromAutomerge for u128 {
-    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        u64::from_automerge(value).map(|u| u as Self)
-    }
+checked_from_automerge! {
+    u64 => u8, u16, u32, usize, u128
 }
 
 impl FromAutomerge for f64 {
@@ -316,49 +532,73 @@ impl FromAutomerge for f64 {
             Ok(*f)
         } else {
             Err(FromAutomergeError::WrongType {
-                found: value.clone(),
+                found: ValueKind::from(value),
                 expected: "a primitive f64".to_owned(),
             })
         }
     }
 }
 
+impl FromAutomerge for f32 {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        let f = f64::from_automerge(value)?;
+        // the cast saturates to infinity so check that the value was in range
+        let narrowed = f as Self;
+        if narrowed.is_infinite() && f.is_finite() {
+            Err(FromAutomergeError::OutOfRange {
+                value: f.to_string(),
+                target_type: "f32",
+            })
+        } else {
+            Ok(narrowed)
+        }
+    }
+}
+
+/// Converts to plain JSON, which loses the distinction between text and strings and between
+/// counters, timestamps and numbers. See [`json`](crate::json) for a lossless encoding.
 impl FromAutomerge for serde_json::Value {
     fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
-        let var_name = match value {
-            Value::Map(map) => Ok(Self::Object(
-                map.iter()
-                    .map(|(k, v)| (k.to_string(), Self::from_automerge(v).unwrap()))
-                    .collect(),
-            )),
-            Value::Table(map) => Ok(Self::Object(
-                map.iter()
-                    .map(|(k, v)| (k.to_string(), Self::from_automerge(v).unwrap()))
-                    .collect(),
-            )),
-            Value::List(v) => Ok(Self::Array(
-                v.iter()
-                    .map(|i| Self::from_automerge(i).unwrap())
-                    .collect::<Vec<_>>(),
-            )),
+        match value {
+            Value::Map(map) | Value::Table(map) => map
+                .iter()
+                .map(|(k, v)| {
+                    Self::from_automerge(v)
+                        .map(|v| (k.to_string(), v))
+                        .map_err(|e| e.at_key(k))
+                })
+                .collect::<Result<_, _>>()
+                .map(Self::Object),
+            Value::List(v) => v
+                .iter()
+                .enumerate()
+                .map(|(i, item)| Self::from_automerge(item).map_err(|e| e.at_index(i)))
+                .collect::<Result<_, _>>()
+                .map(Self::Array),
             Value::Text(v) => Ok(Self::String(v.concat())),
             Value::Primitive(p) => match p {
                 Primitive::Bytes(b) => Ok(Self::Array(
                     b.iter().map(|b| Self::Number(Number::from(*b))).collect(),
                 )),
                 Primitive::Str(s) => Ok(Self::String(s.to_string())),
-                Primitive::Int(i) | Primitive::Counter(i) => Ok(Self::Number(Number::from(*i))),
+                Primitive::Int(i) | Primitive::Counter(i) | Primitive::Timestamp(i) => {
+                    Ok(Self::Number(Number::from(*i)))
+                }
                 Primitive::Uint(u) => Ok(Self::Number(Number::from(*u))),
-                Primitive::F64(f) => Ok(Self::Number(Number::from_f64(*f).unwrap())),
-                Primitive::Timestamp(i) => Ok(Self::Number(Number::from(*i))),
+                Primitive::F64(f) => Number::from_f64(*f).map(Self::Number).ok_or_else(|| {
+                    FromAutomergeError::OutOfRange {
+                        value: f.to_string(),
+                        target_type: "serde_json::Number",
+                    }
+                }),
                 Primitive::Boolean(b) => Ok(Self::Bool(*b)),
-                Primitive::Cursor(_) => {
-                    panic!("cursor is unsupported")
-                }
+                Primitive::Cursor(_) => Err(FromAutomergeError::WrongType {
+                    found: ValueKind::Cursor,
+                    expected: "a value representable in JSON".to_owned(),
+                }),
                 Primitive::Null => Ok(Self::Null),
             },
-        };
-        var_name
+        }
     }
 }
 
@@ -368,15 +608,16 @@ macro_rules! nonzero_to_automerge_unsigned {
         impl FromAutomerge for $x {
             fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
                 if let Value::Primitive(Primitive::Uint(u)) = value {
-                    let val = <$x>::new(*u as $y);
-                    if let Some(val) = val {
-                        Ok(val)
-                    } else {
-                        Err(FromAutomergeError::FailedTryFrom)
-                    }
+                    <$y>::try_from(*u)
+                        .ok()
+                        .and_then(<$x>::new)
+                        .ok_or_else(|| FromAutomergeError::OutOfRange {
+                            value: u.to_string(),
+                            target_type: stringify!($x),
+                        })
                 } else {
                     Err(FromAutomergeError::WrongType {
-                        found: value.clone(),
+                        found: ValueKind::from(value),
                         expected: "a primitive uint".to_owned(),
                     })
                 }
@@ -400,15 +641,16 @@ macro_rules! nonzero_to_automerge_signed {
         impl FromAutomerge for $x {
             fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
                 if let Value::Primitive(Primitive::Int(i)) = value {
-                    let val = <$x>::new(*i as $y);
-                    if let Some(val) = val {
-                        Ok(val)
-                    } else {
-                        Err(FromAutomergeError::FailedTryFrom)
-                    }
+                    <$y>::try_from(*i)
+                        .ok()
+                        .and_then(<$x>::new)
+                        .ok_or_else(|| FromAutomergeError::OutOfRange {
+                            value: i.to_string(),
+                            target_type: stringify!($x),
+                        })
                 } else {
                     Err(FromAutomergeError::WrongType {
-                        found: value.clone(),
+                        found: ValueKind::from(value),
                         expected: "a primitive int".to_owned(),
                     })
                 }
@@ -452,3 +694,13 @@ where
         T::from_automerge(value).map(Self::new)
     }
 }
+
+impl<T> FromAutomerge for Cow<'_, T>
+where
+    T: ToOwned + ?Sized,
+    T::Owned: FromAutomerge,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        T::Owned::from_automerge(value).map(Cow::Owned)
+    }
+}
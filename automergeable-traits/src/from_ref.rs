@@ -0,0 +1,197 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+use automerge::{Primitive, Value};
+use smol_str::SmolStr;
+
+use crate::{Counter, FromAutomerge, FromAutomergeError, Text, Timestamp, ValueKind};
+
+/// Convert from an automerge value, borrowing from it where possible.
+///
+/// Strings, bytes and lists can be decoded without copying them out of the value, so reading a
+/// large document doesn't need to allocate for each field.
+///
+/// This can be derived in the same way as [`FromAutomerge`], borrowing for the first lifetime
+/// parameter of the type.
+///
+/// ```rust
+/// # use automergeable_traits::FromAutomergeRef;
+/// # use automerge::{Primitive, Value};
+/// let value = Value::Primitive(Primitive::Str("hello".into()));
+/// let s = <&str>::from_automerge_ref(&value).unwrap();
+/// assert_eq!(s, "hello");
+/// ```
+pub trait FromAutomergeRef<'a>: Sized {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError>;
+}
+
+impl<'a> FromAutomergeRef<'a> for &'a Value {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        Ok(value)
+    }
+}
+
+impl<'a> FromAutomergeRef<'a> for &'a str {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Primitive(Primitive::Str(s)) = value {
+            Ok(s.as_str())
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a primitive string".to_owned(),
+            })
+        }
+    }
+}
+
+impl<'a> FromAutomergeRef<'a> for &'a [u8] {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Primitive(Primitive::Bytes(b)) = value {
+            Ok(b.as_slice())
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "primitive bytes".to_owned(),
+            })
+        }
+    }
+}
+
+/// Strings are borrowed, text objects are joined into an owned string as their graphemes are
+/// stored separately.
+impl<'a> FromAutomergeRef<'a> for Cow<'a, str> {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        match value {
+            Value::Primitive(Primitive::Str(s)) => Ok(Cow::Borrowed(s.as_str())),
+            Value::Text(graphemes) => Ok(Cow::Owned(graphemes.concat())),
+            _ => Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a primitive string or some text".to_owned(),
+            }),
+        }
+    }
+}
+
+/// The elements of a list, left undecoded.
+impl<'a> FromAutomergeRef<'a> for &'a [Value] {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        if let Value::List(vals) = value {
+            Ok(vals.as_slice())
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a list".to_owned(),
+            })
+        }
+    }
+}
+
+/// The grapheme clusters of a text object.
+impl<'a> FromAutomergeRef<'a> for &'a [SmolStr] {
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Text(graphemes) = value {
+            Ok(graphemes.as_slice())
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "some text".to_owned(),
+            })
+        }
+    }
+}
+
+impl<'a, T> FromAutomergeRef<'a> for Vec<T>
+where
+    T: FromAutomergeRef<'a>,
+{
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        <&[Value]>::from_automerge_ref(value)?
+            .iter()
+            .enumerate()
+            .map(|(i, v)| T::from_automerge_ref(v).map_err(|e| e.at_index(i)))
+            .collect()
+    }
+}
+
+impl<'a, T> FromAutomergeRef<'a> for Option<T>
+where
+    T: FromAutomergeRef<'a>,
+{
+    fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Primitive(Primitive::Null) = value {
+            Ok(None)
+        } else {
+            Ok(Some(T::from_automerge_ref(value)?))
+        }
+    }
+}
+
+/// Maps with their keys borrowed from the value.
+macro_rules! map_from_automerge_ref {
+    ( $( $x:ident ),* $(,)? ) => {
+        $(
+        impl<'a, V> FromAutomergeRef<'a> for $x<&'a str, V>
+        where
+            V: FromAutomergeRef<'a>,
+        {
+            fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+                if let Value::Map(map) = value {
+                    map.iter()
+                        .map(|(k, v)| {
+                            let v = V::from_automerge_ref(v).map_err(|e| e.at_key(k))?;
+                            Ok((k.as_str(), v))
+                        })
+                        .collect()
+                } else {
+                    Err(FromAutomergeError::WrongType {
+                        found: ValueKind::from(value),
+                        expected: "a map".to_owned(),
+                    })
+                }
+            }
+        })*
+    };
+}
+
+map_from_automerge_ref! {
+    HashMap,
+    BTreeMap,
+}
+
+/// Types that don't borrow are decoded as normal.
+macro_rules! owned_from_automerge_ref {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl<'a> FromAutomergeRef<'a> for $x {
+            fn from_automerge_ref(value: &'a Value) -> Result<Self, FromAutomergeError> {
+                <$x>::from_automerge(value)
+            }
+        })*
+    };
+}
+
+owned_from_automerge_ref! {
+    (),
+    bool,
+    char,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    String,
+    Counter,
+    Timestamp,
+    Text,
+}
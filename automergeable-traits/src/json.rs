@@ -0,0 +1,250 @@
+//! A lossless, type-tagged JSON encoding of automerge values.
+//!
+//! Plain JSON can't distinguish text from strings or counters from numbers, so converting a
+//! document through it loses the CRDT types. This encoding keeps them by wrapping values that
+//! have no JSON equivalent in an object with a single tag key:
+//!
+//! | automerge value        | JSON                                     |
+//! |------------------------|------------------------------------------|
+//! | map                    | object                                   |
+//! | table                  | `{"$table": {..}}`                       |
+//! | list                   | array                                    |
+//! | text                   | `{"$text": "some text"}`                 |
+//! | string                 | string                                   |
+//! | int                    | number                                   |
+//! | uint                   | `{"$uint": 5}`                           |
+//! | f64                    | number, or `{"$f64": "NaN"}` for NaN and infinities |
+//! | counter                | `{"$counter": 5}`                        |
+//! | timestamp              | `{"$timestamp": 1612345678000}`          |
+//! | bytes                  | `{"$bytes": "00ff"}` (lowercase hex)     |
+//! | boolean                | boolean                                  |
+//! | null                   | null                                     |
+//!
+//! Map keys starting with `$` are escaped with an extra `$` so they can't be mistaken for tags.
+//! Cursors can't be encoded.
+//!
+//! ```rust
+//! # use automergeable_traits::json::{from_json, to_json};
+//! let value = automerge::Value::Primitive(automerge::Primitive::Counter(5));
+//! let json = to_json(&value).unwrap();
+//! assert_eq!(json, serde_json::json!({"$counter": 5}));
+//! assert_eq!(from_json(&json).unwrap(), value);
+//! ```
+
+use std::{collections::HashMap, convert::TryFrom};
+
+use automerge::{Primitive, Value};
+use serde_json::{Map, Number};
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    to::expect_value, ApplyPatch, FromAutomerge, FromAutomergeError, ToAutomerge, ToAutomergeError,
+};
+
+const TABLE: &str = "$table";
+const TEXT: &str = "$text";
+const UINT: &str = "$uint";
+const F64: &str = "$f64";
+const COUNTER: &str = "$counter";
+const TIMESTAMP: &str = "$timestamp";
+const BYTES: &str = "$bytes";
+
+/// An error converting between automerge values and tagged JSON.
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum JsonError {
+    /// Cursors have no JSON encoding.
+    #[error("cursors can't be encoded as JSON")]
+    Cursor,
+    /// An object had a single `$` key that isn't a known tag.
+    #[error("unknown tag {0}")]
+    UnknownTag(String),
+    /// A tag's content was the wrong type.
+    #[error("invalid content for tag {0}")]
+    InvalidTag(&'static str),
+    /// A number was out of range for an automerge int.
+    #[error("{0} is out of range for an automerge int")]
+    OutOfRange(String),
+}
+
+/// Encode an automerge value as tagged JSON.
+pub fn to_json(value: &Value) -> Result<serde_json::Value, JsonError> {
+    Ok(match value {
+        Value::Map(map) => serde_json::Value::Object(object_to_json(map)?),
+        Value::Table(map) => tagged(TABLE, serde_json::Value::Object(object_to_json(map)?)),
+        Value::List(list) => {
+            serde_json::Value::Array(list.iter().map(to_json).collect::<Result<Vec<_>, _>>()?)
+        }
+        Value::Text(graphemes) => tagged(TEXT, serde_json::Value::String(graphemes.concat())),
+        Value::Primitive(p) => match p {
+            Primitive::Bytes(b) => tagged(BYTES, serde_json::Value::String(to_hex(b))),
+            Primitive::Str(s) => serde_json::Value::String(s.to_string()),
+            Primitive::Int(i) => serde_json::Value::Number(Number::from(*i)),
+            Primitive::Uint(u) => tagged(UINT, serde_json::Value::Number(Number::from(*u))),
+            Primitive::F64(f) => match Number::from_f64(*f) {
+                Some(n) => serde_json::Value::Number(n),
+                None => tagged(F64, serde_json::Value::String(f.to_string())),
+            },
+            Primitive::Counter(i) => tagged(COUNTER, serde_json::Value::Number(Number::from(*i))),
+            Primitive::Timestamp(i) => {
+                tagged(TIMESTAMP, serde_json::Value::Number(Number::from(*i)))
+            }
+            Primitive::Boolean(b) => serde_json::Value::Bool(*b),
+            Primitive::Cursor(_) => return Err(JsonError::Cursor),
+            Primitive::Null => serde_json::Value::Null,
+        },
+    })
+}
+
+/// Decode an automerge value from tagged JSON.
+pub fn from_json(json: &serde_json::Value) -> Result<Value, JsonError> {
+    Ok(match json {
+        serde_json::Value::Null => Value::Primitive(Primitive::Null),
+        serde_json::Value::Bool(b) => Value::Primitive(Primitive::Boolean(*b)),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::Primitive(Primitive::Int(i))
+            } else if let Some(f) = n.as_f64().filter(|_| n.is_f64()) {
+                Value::Primitive(Primitive::F64(f))
+            } else {
+                return Err(JsonError::OutOfRange(n.to_string()));
+            }
+        }
+        serde_json::Value::String(s) => Value::Primitive(Primitive::Str(SmolStr::new(s))),
+        serde_json::Value::Array(items) => {
+            Value::List(items.iter().map(from_json).collect::<Result<Vec<_>, _>>()?)
+        }
+        serde_json::Value::Object(object) => match single_tag(object) {
+            Some((tag, content)) => from_tagged(tag, content)?,
+            None => Value::Map(object_from_json(object)?),
+        },
+    })
+}
+
+fn tagged(tag: &str, content: serde_json::Value) -> serde_json::Value {
+    let mut object = Map::with_capacity(1);
+    object.insert(tag.to_owned(), content);
+    serde_json::Value::Object(object)
+}
+
+/// Get the tag of an object, if it is tagged.
+fn single_tag(object: &Map<String, serde_json::Value>) -> Option<(&str, &serde_json::Value)> {
+    if object.len() != 1 {
+        return None;
+    }
+    object
+        .iter()
+        .next()
+        .filter(|(key, _)| key.starts_with('$') && !key.starts_with("$$"))
+        .map(|(key, content)| (key.as_str(), content))
+}
+
+fn from_tagged(tag: &str, content: &serde_json::Value) -> Result<Value, JsonError> {
+    let int = |tag| content.as_i64().ok_or(JsonError::InvalidTag(tag));
+    Ok(match tag {
+        TABLE => match content {
+            serde_json::Value::Object(object) => Value::Table(object_from_json(object)?),
+            _ => return Err(JsonError::InvalidTag(TABLE)),
+        },
+        TEXT => match content {
+            serde_json::Value::String(s) => {
+                Value::Text(s.graphemes(true).map(SmolStr::new).collect())
+            }
+            _ => return Err(JsonError::InvalidTag(TEXT)),
+        },
+        UINT => Value::Primitive(Primitive::Uint(
+            content.as_u64().ok_or(JsonError::InvalidTag(UINT))?,
+        )),
+        F64 => Value::Primitive(Primitive::F64(
+            content
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .ok_or(JsonError::InvalidTag(F64))?,
+        )),
+        COUNTER => Value::Primitive(Primitive::Counter(int(COUNTER)?)),
+        TIMESTAMP => Value::Primitive(Primitive::Timestamp(int(TIMESTAMP)?)),
+        BYTES => Value::Primitive(Primitive::Bytes(
+            content
+                .as_str()
+                .and_then(from_hex)
+                .ok_or(JsonError::InvalidTag(BYTES))?,
+        )),
+        _ => return Err(JsonError::UnknownTag(tag.to_owned())),
+    })
+}
+
+fn object_to_json(
+    map: &HashMap<SmolStr, Value>,
+) -> Result<Map<String, serde_json::Value>, JsonError> {
+    map.iter()
+        .map(|(k, v)| {
+            let key = if k.starts_with('$') {
+                format!("${}", k)
+            } else {
+                k.to_string()
+            };
+            Ok((key, to_json(v)?))
+        })
+        .collect()
+}
+
+fn object_from_json(
+    object: &Map<String, serde_json::Value>,
+) -> Result<HashMap<SmolStr, Value>, JsonError> {
+    object
+        .iter()
+        .map(|(k, v)| {
+            let key = k
+                .strip_prefix('$')
+                .filter(|k| k.starts_with('$'))
+                .unwrap_or(k);
+            Ok((SmolStr::new(key), from_json(v)?))
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+/// A JSON value in the tagged encoding, converting losslessly to and from automerge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedJson(pub serde_json::Value);
+
+impl ToAutomerge for TaggedJson {
+    fn to_automerge(&self) -> Value {
+        expect_value(self.to_automerge_checked())
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        from_json(&self.0).map_err(|e| ToAutomergeError::Unknown(Box::new(e)))
+    }
+}
+
+impl FromAutomerge for TaggedJson {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        to_json(value)
+            .map(Self)
+            .map_err(|e| FromAutomergeError::Unknown(Box::new(e)))
+    }
+}
+
+impl ApplyPatch for TaggedJson {}
+
+impl TryFrom<&Value> for TaggedJson {
+    type Error = JsonError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        to_json(value).map(Self)
+    }
+}
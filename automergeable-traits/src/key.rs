@@ -0,0 +1,151 @@
+use crate::FromAutomergeError;
+
+/// Types that can be used as the keys of maps, and so the elements of sets.
+///
+/// Automerge maps only have string keys so other types have to be encoded. Unlike `Display` and
+/// `FromStr`, these conversions must be inverses of each other so that the keys round trip.
+///
+/// This can be derived for enums with only unit variants, which are keyed by the variant name,
+/// and for newtype structs, which are keyed like their field.
+pub trait MapKey: Sized {
+    /// Encode this value as a key.
+    ///
+    /// Equal values must have equal keys, and different values different keys.
+    fn to_key(&self) -> String;
+
+    /// Decode the value from a key created by [`to_key`](Self::to_key).
+    fn from_key(key: &str) -> Result<Self, FromAutomergeError>;
+}
+
+impl MapKey for String {
+    fn to_key(&self) -> String {
+        self.clone()
+    }
+
+    fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+        Ok(key.to_owned())
+    }
+}
+
+impl MapKey for Box<str> {
+    fn to_key(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+        Ok(Self::from(key))
+    }
+}
+
+impl MapKey for smol_str::SmolStr {
+    fn to_key(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+        Ok(Self::new(key))
+    }
+}
+
+/// Use the `Display` and `FromStr` implementations, which round trip for these types.
+///
+/// Keys that parse but aren't how the value is displayed, such as `05` or `+5`, are rejected as
+/// two of them could decode to the same value.
+macro_rules! parse_map_key {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl MapKey for $x {
+            fn to_key(&self) -> String {
+                self.to_string()
+            }
+
+            fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+                key.parse::<Self>()
+                    .ok()
+                    .filter(|parsed| parsed.to_key() == key)
+                    .ok_or_else(|| FromAutomergeError::Parse {
+                        value: key.to_owned(),
+                        target_type: stringify!($x),
+                    })
+            }
+        })*
+    };
+}
+
+parse_map_key! {
+    char,
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    std::num::NonZeroI8,
+    std::num::NonZeroI16,
+    std::num::NonZeroI32,
+    std::num::NonZeroI64,
+    std::num::NonZeroI128,
+    std::num::NonZeroIsize,
+    std::num::NonZeroU8,
+    std::num::NonZeroU16,
+    std::num::NonZeroU32,
+    std::num::NonZeroU64,
+    std::num::NonZeroU128,
+    std::num::NonZeroUsize,
+    std::net::IpAddr,
+    std::net::Ipv4Addr,
+    std::net::Ipv6Addr,
+    std::net::SocketAddr,
+    std::net::SocketAddrV4,
+    std::net::SocketAddrV6,
+}
+
+macro_rules! tuple_map_key {
+    ( $( $len:literal => ( $( $t:ident $i:tt ),+ ) ),* $(,)? ) => {
+        $(
+        /// Tuples are keyed by a JSON array of the keys of their fields, such as `["a","1"]`.
+        impl<$( $t ),+> MapKey for ( $( $t, )+ )
+        where
+            $( $t: MapKey, )+
+        {
+            fn to_key(&self) -> String {
+                serde_json::Value::Array(vec![$( serde_json::Value::String(self.$i.to_key()) ),+])
+                    .to_string()
+            }
+
+            fn from_key(key: &str) -> Result<Self, FromAutomergeError> {
+                let invalid = || FromAutomergeError::Parse {
+                    value: key.to_owned(),
+                    target_type: concat!("a tuple of ", $len, " keys"),
+                };
+                let parts = serde_json::from_str::<Vec<String>>(key).map_err(|_| invalid())?;
+                if parts.len() != $len {
+                    return Err(invalid());
+                }
+                Ok(($( $t::from_key(&parts[$i])?, )+))
+            }
+        })*
+    };
+}
+
+tuple_map_key! {
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+    7 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    8 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    9 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    10 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    11 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    12 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11),
+}
@@ -1,13 +1,35 @@
 // #![warn(clippy::pedantic)]
 #![warn(clippy::nursery)]
 
+mod counter;
+mod external;
 mod from;
+mod from_ref;
+pub mod json;
+mod key;
+#[doc(hidden)]
+pub mod marker;
+mod patch;
+mod root;
+mod set;
+mod text;
+mod timestamp;
 mod to;
 
-pub use from::{FromAutomerge, FromAutomergeError, Text};
-pub use to::ToAutomerge;
+pub use counter::Counter;
+pub use from::{ErrorPath, FromAutomerge, FromAutomergeError, PathSegment, ValueKind};
+pub use from_ref::FromAutomergeRef;
+pub use key::MapKey;
+pub use patch::{resolve_diff, ApplyPatch};
+pub use root::{RootAutomergeable, Rooted};
+pub use text::{Text, TextSplice, TextSplices};
+pub use timestamp::{AsTimestamp, Timestamp};
+pub use to::{expect_value, ChangedValues, ToAutomerge, ToAutomergeError, TryToAutomerge};
 
 /// Overall trait for requiring all automerge sub-traits.
-pub trait Automergeable: to::ToAutomerge + from::FromAutomerge {}
+///
+/// Only the fallible [`TryToAutomerge`] conversion is required, which all [`ToAutomerge`] types
+/// have.
+pub trait Automergeable: to::TryToAutomerge + from::FromAutomerge + patch::ApplyPatch {}
 
-impl<T> Automergeable for T where T: to::ToAutomerge + from::FromAutomerge {}
+impl<T> Automergeable for T where T: to::TryToAutomerge + from::FromAutomerge + patch::ApplyPatch {}
@@ -0,0 +1,99 @@
+//! Serde support for the automerge types that serde has no equivalent for.
+//!
+//! Each is serialized as a newtype struct with a reserved name, which `automergeable::serde`
+//! stores as the matching automerge value and other formats treat as just the inner value.
+
+use std::{fmt, marker::PhantomData};
+
+use serde::{
+    de::{Deserialize, Deserializer, Visitor},
+    ser::{Serialize, Serializer},
+};
+
+use crate::{Counter, Text, Timestamp};
+
+/// The newtype name [`Text`] serializes as.
+#[doc(hidden)]
+pub const TEXT: &str = "$automergeable::Text";
+/// The newtype name [`Counter`] serializes as.
+#[doc(hidden)]
+pub const COUNTER: &str = "$automergeable::Counter";
+/// The newtype name [`Timestamp`] serializes as.
+#[doc(hidden)]
+pub const TIMESTAMP: &str = "$automergeable::Timestamp";
+
+/// Visit a marker newtype, wrapping the inner value.
+struct MarkerVisitor<U, T> {
+    expecting: &'static str,
+    wrap: fn(U) -> T,
+    inner: PhantomData<U>,
+}
+
+impl<U, T> MarkerVisitor<U, T> {
+    const fn new(expecting: &'static str, wrap: fn(U) -> T) -> Self {
+        Self {
+            expecting,
+            wrap,
+            inner: PhantomData,
+        }
+    }
+}
+
+impl<'de, U, T> Visitor<'de> for MarkerVisitor<U, T>
+where
+    U: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        U::deserialize(deserializer).map(self.wrap)
+    }
+}
+
+impl Serialize for Text {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(TEXT, &self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_newtype_struct(TEXT, MarkerVisitor::<String, _>::new("text", Text::from))
+    }
+}
+
+impl Serialize for Counter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(COUNTER, &self.value())
+    }
+}
+
+impl<'de> Deserialize<'de> for Counter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_newtype_struct(COUNTER, MarkerVisitor::new("a counter", Counter::new))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(TIMESTAMP, &self.millis())
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(
+            TIMESTAMP,
+            MarkerVisitor::new("a timestamp", Timestamp::from_millis),
+        )
+    }
+}
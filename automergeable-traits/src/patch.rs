@@ -0,0 +1,287 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, VecDeque},
+    hash::Hash,
+    rc::Rc,
+    sync::Arc,
+};
+
+use automerge::{Primitive, Value};
+use automerge_protocol::{Diff, OpId};
+
+use crate::{FromAutomerge, FromAutomergeError, MapKey, PathSegment, TextSplices};
+
+/// Update a typed value in place from the diff of an automerge patch.
+///
+/// Implementations should only touch the parts of the value that the diff refers to, leaving
+/// unaffected subtrees as they are.
+pub trait ApplyPatch: FromAutomerge {
+    /// Apply the `diff` to this value, where `value` is the new automerge value at this location.
+    ///
+    /// The default implementation decodes the whole of `value`.
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        *self = Self::from_automerge(value)?;
+        Ok(())
+    }
+
+    /// Move the splices recorded on any [`Text`](crate::Text) in this value into `splices`, where
+    /// `path` is the path to this value.
+    ///
+    /// The default implementation takes nothing, so changes to texts that aren't reached are
+    /// found by comparing the old and new text instead.
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        let _ = (path, splices);
+    }
+}
+
+/// Get the single diff for a property, if there is no conflict on it.
+///
+/// When multiple operations are present for a property the resolved value can't be tracked from
+/// the diffs alone, so callers should decode the new value in full.
+pub fn resolve_diff(diffs: &HashMap<OpId, Diff>) -> Option<&Diff> {
+    if diffs.len() == 1 {
+        diffs.values().next()
+    } else {
+        None
+    }
+}
+
+macro_rules! decode_apply_patch {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl ApplyPatch for $x {})*
+    };
+}
+
+decode_apply_patch! {
+    Value,
+    (),
+    String,
+    Box<str>,
+    Rc<str>,
+    Arc<str>,
+    std::path::PathBuf,
+    std::net::IpAddr,
+    std::net::Ipv4Addr,
+    std::net::Ipv6Addr,
+    std::net::SocketAddr,
+    std::net::SocketAddrV4,
+    std::net::SocketAddrV6,
+    std::cmp::Ordering,
+    char,
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    isize,
+    i128,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    u128,
+    f32,
+    f64,
+    serde_json::Value,
+    std::num::NonZeroI8,
+    std::num::NonZeroI16,
+    std::num::NonZeroI32,
+    std::num::NonZeroI64,
+    std::num::NonZeroI128,
+    std::num::NonZeroIsize,
+    std::num::NonZeroU8,
+    std::num::NonZeroU16,
+    std::num::NonZeroU32,
+    std::num::NonZeroU64,
+    std::num::NonZeroU128,
+    std::num::NonZeroUsize,
+}
+
+impl<T> ApplyPatch for Vec<T>
+where
+    T: ApplyPatch,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        match (diff, value) {
+            // without edits the indices of existing elements are stable so we can update them
+            // in place
+            (Diff::Seq(seq_diff), Value::List(vals))
+                if seq_diff.edits.is_empty() && vals.len() == self.len() =>
+            {
+                for (index, diffs) in &seq_diff.props {
+                    match (self.get_mut(*index), vals.get(*index)) {
+                        (Some(item), Some(value)) => {
+                            let result = if let Some(diff) = resolve_diff(diffs) {
+                                item.apply_patch(diff, value)
+                            } else {
+                                T::from_automerge(value).map(|value| *item = value)
+                            };
+                            result.map_err(|e| e.at_index(*index))?
+                        }
+                        _ => {
+                            *self = Self::from_automerge(value)?;
+                            return Ok(());
+                        }
+                    }
+                }
+                Ok(())
+            }
+            _ => {
+                *self = Self::from_automerge(value)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        for (index, item) in self.iter_mut().enumerate() {
+            path.push(PathSegment::Index(index));
+            item.take_splices(path, splices);
+            path.pop();
+        }
+    }
+}
+
+// elements may have moved so these are decoded again in full
+impl<T> ApplyPatch for VecDeque<T> where T: FromAutomerge {}
+
+impl<T, const N: usize> ApplyPatch for [T; N] where T: FromAutomerge {}
+
+macro_rules! tuple_apply_patch {
+    ( $( ( $( $t:ident ),+ ) ),* $(,)? ) => {
+        $(
+        impl<$( $t ),+> ApplyPatch for ( $( $t, )+ ) where $( $t: FromAutomerge, )+ {})*
+    };
+}
+
+tuple_apply_patch! {
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L),
+}
+
+impl<K, V> ApplyPatch for HashMap<K, V>
+where
+    K: MapKey + Eq + Hash,
+    V: ApplyPatch,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        if let (Diff::Map(map_diff), Value::Map(vals)) = (diff, value) {
+            for (key, diffs) in &map_diff.props {
+                let k = K::from_key(key).map_err(|e| e.at_key(key))?;
+                if let Some(value) = vals.get(key.as_str()) {
+                    match (self.get_mut(&k), resolve_diff(diffs)) {
+                        (Some(existing), Some(diff)) => existing
+                            .apply_patch(diff, value)
+                            .map_err(|e| e.at_key(key))?,
+                        _ => {
+                            self.insert(k, V::from_automerge(value).map_err(|e| e.at_key(key))?);
+                        }
+                    }
+                } else {
+                    self.remove(&k);
+                }
+            }
+            Ok(())
+        } else {
+            *self = Self::from_automerge(value)?;
+            Ok(())
+        }
+    }
+}
+
+impl<K, V> ApplyPatch for BTreeMap<K, V>
+where
+    K: MapKey + Eq + Ord,
+    V: ApplyPatch,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        if let (Diff::Map(map_diff), Value::Map(vals)) = (diff, value) {
+            for (key, diffs) in &map_diff.props {
+                let k = K::from_key(key).map_err(|e| e.at_key(key))?;
+                if let Some(value) = vals.get(key.as_str()) {
+                    match (self.get_mut(&k), resolve_diff(diffs)) {
+                        (Some(existing), Some(diff)) => existing
+                            .apply_patch(diff, value)
+                            .map_err(|e| e.at_key(key))?,
+                        _ => {
+                            self.insert(k, V::from_automerge(value).map_err(|e| e.at_key(key))?);
+                        }
+                    }
+                } else {
+                    self.remove(&k);
+                }
+            }
+            Ok(())
+        } else {
+            *self = Self::from_automerge(value)?;
+            Ok(())
+        }
+    }
+}
+
+impl<T> ApplyPatch for Option<T>
+where
+    T: ApplyPatch,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        match self {
+            Some(inner) if !matches!(value, Value::Primitive(Primitive::Null)) => {
+                inner.apply_patch(diff, value)
+            }
+            _ => {
+                *self = Self::from_automerge(value)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        if let Some(inner) = self {
+            inner.take_splices(path, splices)
+        }
+    }
+}
+
+impl<T, E> ApplyPatch for Result<T, E>
+where
+    T: FromAutomerge,
+    E: FromAutomerge,
+{
+}
+
+impl<T> ApplyPatch for Box<T>
+where
+    T: ApplyPatch,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        (**self).apply_patch(diff, value)
+    }
+
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        (**self).take_splices(path, splices)
+    }
+}
+
+// shared pointers can't be updated in place so just decode them again
+impl<T> ApplyPatch for Rc<T> where T: FromAutomerge {}
+
+impl<T> ApplyPatch for Arc<T> where T: FromAutomerge {}
+
+impl<T> ApplyPatch for Cow<'_, T>
+where
+    T: ToOwned + ?Sized,
+    T::Owned: FromAutomerge,
+{
+}
@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ops::{Deref, DerefMut},
+};
+
+use automerge::Value;
+use automerge_protocol::Diff;
+
+use crate::{
+    resolve_diff, ApplyPatch, Automergeable, ChangedValues, FromAutomerge, FromAutomergeError,
+    PathSegment, TextSplices, ToAutomerge, ToAutomergeError, ValueKind,
+};
+
+/// Marker for types that convert to and from an automerge map, so can be used as the root of a
+/// document.
+///
+/// This is derived alongside [`Automergeable`] for structs with named fields. Other types can be
+/// stored in a document by wrapping them in [`Rooted`].
+pub trait RootAutomergeable: Automergeable {}
+
+impl<K, V> RootAutomergeable for HashMap<K, V> where Self: Automergeable {}
+
+impl<K, V> RootAutomergeable for BTreeMap<K, V> where Self: Automergeable {}
+
+impl<T> RootAutomergeable for HashSet<T> where Self: Automergeable {}
+
+impl<T> RootAutomergeable for BTreeSet<T> where Self: Automergeable {}
+
+impl<T> RootAutomergeable for Box<T> where T: RootAutomergeable {}
+
+/// Stores a value that isn't a map under a fixed key in the root of a document.
+///
+/// ```rust
+/// # use automergeable_traits::{Rooted, ToAutomerge};
+/// let rooted = Rooted(vec!["a".to_owned()]);
+/// let value = rooted.to_automerge();
+/// assert!(matches!(value, automerge::Value::Map(map) if map.contains_key(Rooted::<()>::KEY)));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rooted<T>(pub T);
+
+impl<T> Rooted<T> {
+    /// The key in the root map that the value is stored under.
+    pub const KEY: &'static str = "value";
+
+    /// Unwrap the inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Rooted<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> Deref for Rooted<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Rooted<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> ToAutomerge for Rooted<T>
+where
+    T: ToAutomerge,
+{
+    fn to_automerge(&self) -> Value {
+        let mut map = HashMap::with_capacity(1);
+        map.insert(Self::KEY.into(), self.0.to_automerge());
+        Value::Map(map)
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let mut map = HashMap::with_capacity(1);
+        map.insert(Self::KEY.into(), self.0.to_automerge_checked()?);
+        Ok(Value::Map(map))
+    }
+
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        path.push(PathSegment::Key(Self::KEY.to_owned()));
+        self.0.to_automerge_changed(&old.0, path, changed)?;
+        path.pop();
+        Ok(())
+    }
+}
+
+impl<T> FromAutomerge for Rooted<T>
+where
+    T: FromAutomerge + Default,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Map(map) = value {
+            // a new document is empty so treat it like a missing field
+            let inner = match map.get(Self::KEY) {
+                Some(inner) => T::from_automerge(inner).map_err(|e| e.at_key(Self::KEY))?,
+                None => T::default(),
+            };
+            Ok(Self(inner))
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "a map".to_owned(),
+            })
+        }
+    }
+}
+
+impl<T> ApplyPatch for Rooted<T>
+where
+    T: ApplyPatch + Default,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        match (diff, value) {
+            (Diff::Map(map_diff), Value::Map(map)) => {
+                if let Some(diffs) = map_diff.props.get(Self::KEY) {
+                    let result = match (map.get(Self::KEY), resolve_diff(diffs)) {
+                        (Some(inner), Some(diff)) => self.0.apply_patch(diff, inner),
+                        (Some(inner), None) => T::from_automerge(inner).map(|inner| self.0 = inner),
+                        (None, _) => {
+                            self.0 = T::default();
+                            Ok(())
+                        }
+                    };
+                    result.map_err(|e| e.at_key(Self::KEY))?;
+                }
+                Ok(())
+            }
+            _ => {
+                *self = Self::from_automerge(value)?;
+                Ok(())
+            }
+        }
+    }
+
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        path.push(PathSegment::Key(Self::KEY.to_owned()));
+        self.0.take_splices(path, splices);
+        path.pop();
+    }
+}
+
+impl<T> RootAutomergeable for Rooted<T> where Self: Automergeable {}
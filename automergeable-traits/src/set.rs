@@ -0,0 +1,161 @@
+//! Sets are stored as a map from the [`MapKey`] of each element to `true`. Concurrent inserts of
+//! the same element then merge into a single entry, and removing an element deletes its key.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    hash::Hash,
+    iter::FromIterator,
+};
+
+use automerge::{Primitive, Value};
+use automerge_protocol::Diff;
+use smol_str::SmolStr;
+
+use crate::{ApplyPatch, FromAutomerge, FromAutomergeError, MapKey, ToAutomerge, ValueKind};
+
+/// Encode the elements of a set as a map value.
+pub(crate) fn set_to_automerge<'a, T>(elements: impl IntoIterator<Item = &'a T>) -> Value
+where
+    T: MapKey + 'a,
+{
+    let map = elements
+        .into_iter()
+        .map(|element| {
+            (
+                SmolStr::new(element.to_key()),
+                Value::Primitive(Primitive::Boolean(true)),
+            )
+        })
+        .collect();
+    Value::Map(map)
+}
+
+/// Decode the elements of a set from a map value.
+pub(crate) fn set_from_automerge<T, S>(value: &Value) -> Result<S, FromAutomergeError>
+where
+    T: MapKey,
+    S: FromIterator<T>,
+{
+    if let Value::Map(map) = value {
+        map.iter()
+            .map(|(key, present)| match present {
+                Value::Primitive(Primitive::Boolean(true)) => {
+                    T::from_key(key).map_err(|e| e.at_key(key))
+                }
+                _ => Err(FromAutomergeError::WrongType {
+                    found: ValueKind::from(present),
+                    expected: "a primitive boolean true".to_owned(),
+                }
+                .at_key(key)),
+            })
+            .collect()
+    } else {
+        Err(FromAutomergeError::WrongType {
+            found: ValueKind::from(value),
+            expected: "a map".to_owned(),
+        })
+    }
+}
+
+/// Get the elements changed by a map diff, along with whether they are now in the set.
+///
+/// Returns `None` if the diff isn't for a map, in which case the set should be decoded again.
+pub(crate) fn set_changes<T>(
+    diff: &Diff,
+    value: &Value,
+) -> Result<Option<Vec<(T, bool)>>, FromAutomergeError>
+where
+    T: MapKey,
+{
+    if let (Diff::Map(map_diff), Value::Map(vals)) = (diff, value) {
+        map_diff
+            .props
+            .keys()
+            .map(|key| {
+                let element = T::from_key(key).map_err(|e| e.at_key(key))?;
+                Ok((element, vals.contains_key(key.as_str())))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Hash sets are stored as a map from the [`MapKey`] of each element to `true`.
+impl<T> ToAutomerge for HashSet<T>
+where
+    T: MapKey,
+{
+    fn to_automerge(&self) -> Value {
+        set_to_automerge(self.iter())
+    }
+}
+
+impl<T> FromAutomerge for HashSet<T>
+where
+    T: MapKey + Eq + Hash,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        set_from_automerge(value)
+    }
+}
+
+impl<T> ApplyPatch for HashSet<T>
+where
+    T: MapKey + Eq + Hash,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        if let Some(changes) = set_changes(diff, value)? {
+            for (element, present) in changes {
+                if present {
+                    self.insert(element);
+                } else {
+                    self.remove(&element);
+                }
+            }
+        } else {
+            *self = Self::from_automerge(value)?;
+        }
+        Ok(())
+    }
+}
+
+/// B-tree sets are stored as a map from the [`MapKey`] of each element to `true`.
+impl<T> ToAutomerge for BTreeSet<T>
+where
+    T: MapKey,
+{
+    fn to_automerge(&self) -> Value {
+        set_to_automerge(self.iter())
+    }
+}
+
+impl<T> FromAutomerge for BTreeSet<T>
+where
+    T: MapKey + Ord,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        set_from_automerge(value)
+    }
+}
+
+impl<T> ApplyPatch for BTreeSet<T>
+where
+    T: MapKey + Ord,
+{
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        if let Some(changes) = set_changes(diff, value)? {
+            for (element, present) in changes {
+                if present {
+                    self.insert(element);
+                } else {
+                    self.remove(&element);
+                }
+            }
+        } else {
+            *self = Self::from_automerge(value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    mem,
+    str::FromStr,
+};
+
+use automerge::Value;
+use automerge_protocol::{Diff, DiffEdit};
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{ApplyPatch, FromAutomerge, FromAutomergeError, PathSegment, ToAutomerge, ValueKind};
+
+/// Text stored as an automerge text object, edited by grapheme cluster.
+///
+/// Unlike a `String` field with `representation = "text"`, edits only touch the graphemes they
+/// refer to, so a keystroke in a document becomes a single insert or delete operation.
+///
+/// ```rust
+/// # use automergeable_traits::Text;
+/// let mut text = Text::from("hello");
+/// text.insert(5, " world");
+/// text.splice(0, 1, "H");
+/// text.delete(5, 6);
+/// assert_eq!(text.to_string(), "Hello");
+/// assert_eq!(text.len(), 5);
+/// ```
+///
+/// Each edit is also recorded as a [`TextSplice`], so a document turns it into exactly the
+/// operations that were made, rather than guessing them by comparing the old and new text. At
+/// most [`Text::MAX_SPLICES`] are kept until they are taken, past that they are dropped and the
+/// text is compared instead.
+#[derive(Debug, Default, Clone)]
+pub struct Text {
+    graphemes: Vec<SmolStr>,
+    splices: Vec<TextSplice>,
+}
+
+/// An edit made to some [`Text`], replacing `delete` graphemes at `index` with `insert`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextSplice {
+    /// The index of the first grapheme deleted, or where to insert.
+    pub index: usize,
+    /// The number of graphemes deleted.
+    pub delete: usize,
+    /// The graphemes inserted at `index`.
+    pub insert: Vec<SmolStr>,
+}
+
+impl TextSplice {
+    /// Make this edit to `graphemes`, returning `None` if it is out of range.
+    pub fn apply(&self, graphemes: &mut Vec<SmolStr>) -> Option<()> {
+        let end = self.index.checked_add(self.delete)?;
+        if end > graphemes.len() {
+            return None;
+        }
+        graphemes.splice(self.index..end, self.insert.iter().cloned());
+        Some(())
+    }
+}
+
+/// The splices taken from the texts in a value, by the path to each text.
+///
+/// See [`ApplyPatch::take_splices`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TextSplices(Vec<(Vec<PathSegment>, Vec<TextSplice>)>);
+
+impl TextSplices {
+    /// The splices made to the text at `path`, in the order they were made.
+    pub fn get(&self, path: &[PathSegment]) -> Option<&[TextSplice]> {
+        self.0
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, splices)| splices.as_slice())
+    }
+
+    /// Whether no splices were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Text {
+    /// The most splices recorded before they are dropped, so text that is edited outside of a
+    /// document doesn't keep a growing history.
+    pub const MAX_SPLICES: usize = 1024;
+
+    /// Create some empty text.
+    pub const fn new() -> Self {
+        Self {
+            graphemes: Vec::new(),
+            splices: Vec::new(),
+        }
+    }
+
+    /// The number of grapheme clusters.
+    pub fn len(&self) -> usize {
+        self.graphemes.len()
+    }
+
+    /// Whether there are no graphemes.
+    pub fn is_empty(&self) -> bool {
+        self.graphemes.is_empty()
+    }
+
+    /// The grapheme clusters making up the text.
+    pub fn graphemes(&self) -> &[SmolStr] {
+        &self.graphemes
+    }
+
+    /// Get the grapheme at `index`.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.graphemes.get(index).map(SmolStr::as_str)
+    }
+
+    /// Insert `s` before the grapheme at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length.
+    pub fn insert(&mut self, index: usize, s: &str) {
+        self.splice(index, 0, s)
+    }
+
+    /// Delete `count` graphemes, starting at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range to delete extends past the end of the text.
+    pub fn delete(&mut self, index: usize, count: usize) {
+        self.splice(index, count, "")
+    }
+
+    /// Replace `delete` graphemes, starting at `index`, with `s`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range to delete extends past the end of the text.
+    pub fn splice(&mut self, index: usize, delete: usize, s: &str) {
+        let insert = s.graphemes(true).map(SmolStr::new).collect::<Vec<_>>();
+        self.graphemes
+            .splice(index..index + delete, insert.iter().cloned());
+        if delete > 0 || !insert.is_empty() {
+            if self.splices.len() >= Self::MAX_SPLICES {
+                // the later splices alone rarely make the new text, so a document compares it
+                self.splices.clear();
+            }
+            self.splices.push(TextSplice {
+                index,
+                delete,
+                insert,
+            })
+        }
+    }
+
+    /// Append `s` to the end of the text.
+    pub fn push_str(&mut self, s: &str) {
+        self.splice(self.len(), 0, s)
+    }
+
+    /// The splices made since they were last taken, in the order they were made.
+    pub fn splices(&self) -> &[TextSplice] {
+        &self.splices
+    }
+}
+
+// the recorded splices are how the text got here, not part of its value
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.graphemes == other.graphemes
+    }
+}
+
+impl Eq for Text {}
+
+impl Hash for Text {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.graphemes.hash(state)
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for grapheme in &self.graphemes {
+            f.write_str(grapheme)?
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for Text {
+    fn from(s: &str) -> Self {
+        Self {
+            graphemes: s.graphemes(true).map(SmolStr::new).collect(),
+            splices: Vec::new(),
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(s: String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl From<Text> for String {
+    fn from(text: Text) -> Self {
+        text.graphemes.concat()
+    }
+}
+
+impl FromStr for Text {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from(s))
+    }
+}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        // compare without allocating by walking the graphemes along the string
+        let mut rest = other;
+        for grapheme in &self.graphemes {
+            match rest.strip_prefix(grapheme.as_str()) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        }
+        rest.is_empty()
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl ToAutomerge for Text {
+    fn to_automerge(&self) -> Value {
+        Value::Text(self.graphemes.clone())
+    }
+}
+
+impl FromAutomerge for Text {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        if let Value::Text(graphemes) = value {
+            Ok(Self {
+                graphemes: graphemes.clone(),
+                splices: Vec::new(),
+            })
+        } else {
+            Err(FromAutomergeError::WrongType {
+                found: ValueKind::from(value),
+                expected: "some text".to_owned(),
+            })
+        }
+    }
+}
+
+/// Inserts and deletes from the patch are made in place, taking the new graphemes from `value`.
+impl ApplyPatch for Text {
+    fn apply_patch(&mut self, diff: &Diff, value: &Value) -> Result<(), FromAutomergeError> {
+        if let (Diff::Seq(seq_diff), Value::Text(graphemes)) = (diff, value) {
+            let mut in_place = true;
+            for edit in &seq_diff.edits {
+                match edit {
+                    DiffEdit::Insert { index, .. } if *index <= self.graphemes.len() => {
+                        self.graphemes.insert(*index, SmolStr::default())
+                    }
+                    DiffEdit::Remove { index } if *index < self.graphemes.len() => {
+                        self.graphemes.remove(*index);
+                    }
+                    _ => {
+                        in_place = false;
+                        break;
+                    }
+                }
+            }
+            if in_place {
+                for index in seq_diff.props.keys() {
+                    if let (Some(grapheme), Some(new)) =
+                        (self.graphemes.get_mut(*index), graphemes.get(*index))
+                    {
+                        *grapheme = new.clone();
+                    }
+                }
+                if self.graphemes == *graphemes {
+                    return Ok(());
+                }
+            }
+        }
+        let splices = mem::take(&mut self.splices);
+        *self = Self::from_automerge(value)?;
+        self.splices = splices;
+        Ok(())
+    }
+
+    fn take_splices(&mut self, path: &mut Vec<PathSegment>, splices: &mut TextSplices) {
+        if !self.splices.is_empty() {
+            splices.0.push((path.clone(), mem::take(&mut self.splices)))
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::convert::TryFrom;
+
+use automerge::{Primitive, Value};
+
+use crate::{
+    to::expect_value, ApplyPatch, FromAutomerge, FromAutomergeError, ToAutomerge, ToAutomergeError,
+    ValueKind,
+};
+
+/// Types that can be stored as an automerge timestamp, in milliseconds since the unix epoch.
+///
+/// Milliseconds match how automerge and JavaScript clients interpret timestamps. Any precision
+/// finer than a millisecond is dropped.
+///
+/// These types can be used with `#[automergeable(representation = "timestamp")]` fields.
+///
+/// # Breaking change
+///
+/// Earlier versions stored `SystemTime` as whole seconds. Those timestamps now decode as a time
+/// in early 1970, read them as an `i64` and multiply by 1000 to migrate a document, see the
+/// changelog.
+pub trait AsTimestamp: Sized {
+    /// The number of milliseconds since the unix epoch.
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError>;
+
+    /// Construct the value from a number of milliseconds since the unix epoch.
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError>;
+}
+
+impl AsTimestamp for i64 {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        Ok(*self)
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        Ok(millis)
+    }
+}
+
+/// An automerge timestamp, in milliseconds since the unix epoch.
+///
+/// Use this for times that don't need a date and time library, or to store them exactly as
+/// automerge and JavaScript clients see them.
+///
+/// ```rust
+/// # use automergeable_traits::Timestamp;
+/// let created = Timestamp::from_millis(1_612_345_678_000);
+/// assert_eq!(created.millis(), 1_612_345_678_000);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    /// Create a timestamp from a number of milliseconds since the unix epoch.
+    pub const fn from_millis(millis: i64) -> Self {
+        Self(millis)
+    }
+
+    /// The number of milliseconds since the unix epoch.
+    pub const fn millis(&self) -> i64 {
+        self.0
+    }
+
+    /// The current time.
+    #[cfg(feature = "std")]
+    pub fn now() -> Self {
+        let millis = std::time::SystemTime::now()
+            .to_timestamp()
+            .expect("the current time fits in a timestamp");
+        Self(millis)
+    }
+}
+
+impl AsTimestamp for Timestamp {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        Ok(self.0)
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        Ok(Self(millis))
+    }
+}
+
+fn timestamp_out_of_range(value: &impl std::fmt::Debug) -> ToAutomergeError {
+    ToAutomergeError::OutOfRange {
+        value: format!("{:?}", value),
+        target_type: "Timestamp",
+    }
+}
+
+fn millis_out_of_range(millis: i64, target_type: &'static str) -> FromAutomergeError {
+    FromAutomergeError::OutOfRange {
+        value: millis.to_string(),
+        target_type,
+    }
+}
+
+#[cfg(feature = "std")]
+impl AsTimestamp for std::time::SystemTime {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        // times before the epoch are negative
+        let millis = match self.duration_since(Self::UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_millis()),
+            Err(before) => i64::try_from(before.duration().as_millis()).map(|m| -m),
+        };
+        millis.map_err(|_| timestamp_out_of_range(self))
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        let offset = std::time::Duration::from_millis(millis.unsigned_abs());
+        let time = if millis >= 0 {
+            Self::UNIX_EPOCH.checked_add(offset)
+        } else {
+            Self::UNIX_EPOCH.checked_sub(offset)
+        };
+        time.ok_or_else(|| millis_out_of_range(millis, "std::time::SystemTime"))
+    }
+}
+
+/// A duration as a timestamp is the time since the unix epoch.
+#[cfg(feature = "std")]
+impl AsTimestamp for std::time::Duration {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        i64::try_from(self.as_millis()).map_err(|_| timestamp_out_of_range(self))
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        u64::try_from(millis)
+            .map(Self::from_millis)
+            .map_err(|_| millis_out_of_range(millis, "std::time::Duration"))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl AsTimestamp for chrono::DateTime<chrono::Utc> {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        Ok(self.timestamp_millis())
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        use chrono::TimeZone;
+        chrono::Utc
+            .timestamp_millis_opt(millis)
+            .single()
+            .ok_or_else(|| millis_out_of_range(millis, "chrono::DateTime<Utc>"))
+    }
+}
+
+#[cfg(feature = "time")]
+impl AsTimestamp for time::OffsetDateTime {
+    fn to_timestamp(&self) -> Result<i64, ToAutomergeError> {
+        i64::try_from(self.unix_timestamp_nanos() / 1_000_000)
+            .map_err(|_| timestamp_out_of_range(self))
+    }
+
+    fn from_timestamp(millis: i64) -> Result<Self, FromAutomergeError> {
+        Self::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)
+            .map_err(|_| millis_out_of_range(millis, "time::OffsetDateTime"))
+    }
+}
+
+/// Store points in time as automerge timestamps.
+macro_rules! timestamp_automerge {
+    ( $( $(#[$attr:meta])* $x:ty ),* $(,)? ) => {
+        $(
+        $(#[$attr])*
+        impl ToAutomerge for $x {
+            fn to_automerge(&self) -> Value {
+                expect_value(self.to_automerge_checked())
+            }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                Ok(Value::Primitive(Primitive::Timestamp(self.to_timestamp()?)))
+            }
+        }
+
+        $(#[$attr])*
+        impl FromAutomerge for $x {
+            fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+                if let Value::Primitive(Primitive::Timestamp(t)) = value {
+                    Self::from_timestamp(*t)
+                } else {
+                    Err(FromAutomergeError::WrongType {
+                        found: ValueKind::from(value),
+                        expected: "a primitive timestamp".to_owned(),
+                    })
+                }
+            }
+        }
+
+        $(#[$attr])*
+        impl ApplyPatch for $x {}
+        )*
+    };
+}
+
+timestamp_automerge! {
+    Timestamp,
+    #[cfg(feature = "std")]
+    std::time::SystemTime,
+    #[cfg(feature = "chrono")]
+    chrono::DateTime<chrono::Utc>,
+    #[cfg(feature = "time")]
+    time::OffsetDateTime,
+}
+
+/// Durations are stored as a number of milliseconds.
+#[cfg(feature = "std")]
+impl ToAutomerge for std::time::Duration {
+    fn to_automerge(&self) -> Value {
+        expect_value(self.to_automerge_checked())
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let millis = u64::try_from(self.as_millis()).map_err(|_| ToAutomergeError::OutOfRange {
+            value: format!("{:?}", self),
+            target_type: "Uint",
+        })?;
+        Ok(Value::Primitive(Primitive::Uint(millis)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl FromAutomerge for std::time::Duration {
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        u64::from_automerge(value).map(Self::from_millis)
+    }
+}
+
+#[cfg(feature = "std")]
+impl ApplyPatch for std::time::Duration {}
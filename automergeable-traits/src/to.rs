@@ -1,6 +1,12 @@
 use std::{
-    collections::{BTreeMap, HashMap},
-    convert::TryInto,
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::TryFrom,
+    error::Error,
+    hash::Hash,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
 };
@@ -8,9 +14,147 @@ use std::{
 use automerge::{Primitive, Value};
 use smol_str::SmolStr;
 
+use crate::{MapKey, PathSegment};
+
 /// Require a method to convert the current value into an automerge value.
 pub trait ToAutomerge {
     fn to_automerge(&self) -> Value;
+
+    /// Convert the current value into an automerge value, failing if it can't be represented.
+    ///
+    /// Types with values that automerge can't hold, or shouldn't store, override this to report
+    /// them. [`to_automerge`](Self::to_automerge) doesn't check: it panics on values with no
+    /// representation at all, such as out of range integers, but passes others such as `NaN`
+    /// straight through. This is usually used through [`TryToAutomerge`].
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        Ok(self.to_automerge())
+    }
+
+    /// Convert the parts of the current value that may differ from `old`, an earlier version of
+    /// it, adding them to `changed` by their path below `path`.
+    ///
+    /// The default converts the whole value. Structs, maps and same length lists pass this on to
+    /// their elements, and [`Rc`] and [`Arc`] skip values that are still shared with `old`, so a
+    /// change only converts and compares the parts it touched.
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        let _ = old;
+        changed.push(path, Some(self.to_automerge_checked()?));
+        Ok(())
+    }
+}
+
+/// Require a method to convert the current value into an automerge value, which may fail.
+///
+/// This is implemented for all [`ToAutomerge`] types through
+/// [`ToAutomerge::to_automerge_checked`]. Implement it directly (or derive it) for types that
+/// can't implement [`ToAutomerge`], such as those with fields that only implement this.
+pub trait TryToAutomerge {
+    fn try_to_automerge(&self) -> Result<Value, ToAutomergeError>;
+
+    /// Convert the parts of the current value that may differ from `old`, see
+    /// [`ToAutomerge::to_automerge_changed`].
+    ///
+    /// The default converts the whole value.
+    fn try_to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        let _ = old;
+        changed.push(path, Some(self.try_to_automerge()?));
+        Ok(())
+    }
+}
+
+impl<T> TryToAutomerge for T
+where
+    T: ToAutomerge + ?Sized,
+{
+    fn try_to_automerge(&self) -> Result<Value, ToAutomergeError> {
+        self.to_automerge_checked()
+    }
+
+    fn try_to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        self.to_automerge_changed(old, path, changed)
+    }
+}
+
+/// The converted values of the parts of a value that may have changed, by their path.
+///
+/// A value of `None` is a map entry that was removed. See
+/// [`ToAutomerge::to_automerge_changed`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ChangedValues(Vec<(Vec<PathSegment>, Option<Value>)>);
+
+impl ChangedValues {
+    /// Record the new value at `path`, or that it was removed.
+    pub fn push(&mut self, path: &[PathSegment], value: Option<Value>) {
+        self.0.push((path.to_vec(), value))
+    }
+
+    /// The changed values, in the order they were found.
+    pub fn iter(&self) -> impl Iterator<Item = (&[PathSegment], Option<&Value>)> {
+        self.0
+            .iter()
+            .map(|(path, value)| (path.as_slice(), value.as_ref()))
+    }
+
+    /// Whether nothing may have changed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl IntoIterator for ChangedValues {
+    type Item = (Vec<PathSegment>, Option<Value>);
+    type IntoIter = std::vec::IntoIter<Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// A failure converting a Rust type to an automerge value.
+#[derive(thiserror::Error, Debug)]
+pub enum ToAutomergeError {
+    #[error("{value} is out of range for {target_type}")]
+    OutOfRange {
+        /// The value that couldn't be converted.
+        value: String,
+        /// The automerge type it couldn't be converted into.
+        target_type: &'static str,
+    },
+    #[error("NaN can't be stored as it isn't equal to itself")]
+    NotANumber,
+    /// Strings in automerge have to be valid unicode.
+    #[error("{0} isn't valid unicode")]
+    NotUnicode(String),
+    #[error("unknown error: {0}")]
+    Unknown(#[from] Box<dyn Error + Send + Sync>),
+}
+
+/// Unwrap the result of a fallible conversion, for implementing [`ToAutomerge`] on top of
+/// [`TryToAutomerge`].
+///
+/// # Panics
+///
+/// If the conversion failed, with the error as the message.
+pub fn expect_value(result: Result<Value, ToAutomergeError>) -> Value {
+    match result {
+        Ok(value) => value,
+        Err(e) => panic!("{}", e),
+    }
 }
 
 impl ToAutomerge for Value {
@@ -33,44 +177,212 @@ where
         let vals = self.iter().map(|v| v.to_automerge()).collect::<Vec<_>>();
         Value::List(vals)
     }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let vals = self
+            .iter()
+            .map(|v| v.to_automerge_checked())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(vals))
+    }
+
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        // items may have moved if the length changed so the list is compared as a whole
+        if self.len() != old.len() {
+            changed.push(path, Some(self.to_automerge_checked()?));
+            return Ok(());
+        }
+        for (index, (new, old)) in self.iter().zip(old).enumerate() {
+            path.push(PathSegment::Index(index));
+            new.to_automerge_changed(old, path, changed)?;
+            path.pop();
+        }
+        Ok(())
+    }
 }
 
-// impl<T> ToAutomerge for HashSet<T>
-// where
-//     T: ToAutomerge,
-// {
-//     fn to_automerge(&self) -> Value {
-//         let vals = self.iter().map(|v| v.to_automerge()).collect::<Vec<_>>();
-//         Value::List(vals)
-//     }
-// }
+impl<T> ToAutomerge for VecDeque<T>
+where
+    T: ToAutomerge,
+{
+    fn to_automerge(&self) -> Value {
+        let vals = self.iter().map(|v| v.to_automerge()).collect::<Vec<_>>();
+        Value::List(vals)
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let vals = self
+            .iter()
+            .map(|v| v.to_automerge_checked())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(vals))
+    }
+}
+
+/// Arrays are stored as a list.
+impl<T, const N: usize> ToAutomerge for [T; N]
+where
+    T: ToAutomerge,
+{
+    fn to_automerge(&self) -> Value {
+        let vals = self.iter().map(|v| v.to_automerge()).collect::<Vec<_>>();
+        Value::List(vals)
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let vals = self
+            .iter()
+            .map(|v| v.to_automerge_checked())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Value::List(vals))
+    }
+}
+
+macro_rules! tuple_to_automerge {
+    ( $( ( $( $t:ident $i:tt ),+ ) ),* $(,)? ) => {
+        $(
+        /// Tuples are stored as a list with an element for each field.
+        impl<$( $t ),+> ToAutomerge for ( $( $t, )+ )
+        where
+            $( $t: ToAutomerge, )+
+        {
+            fn to_automerge(&self) -> Value {
+                Value::List(vec![$( self.$i.to_automerge() ),+])
+            }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                Ok(Value::List(vec![$( self.$i.to_automerge_checked()? ),+]))
+            }
+        })*
+    };
+}
+
+tuple_to_automerge! {
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+    (A 0, B 1, C 2, D 3, E 4),
+    (A 0, B 1, C 2, D 3, E 4, F 5),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11),
+}
 
 impl<K, V> ToAutomerge for HashMap<K, V>
 where
-    K: ToString,
+    K: MapKey + Eq + Hash,
     V: ToAutomerge,
 {
     fn to_automerge(&self) -> Value {
         let mut hm = HashMap::with_capacity(self.len());
         for (k, v) in self {
-            hm.insert(k.to_string().into(), v.to_automerge());
+            hm.insert(k.to_key().into(), v.to_automerge());
         }
         Value::Map(hm)
     }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let mut hm = HashMap::with_capacity(self.len());
+        for (k, v) in self {
+            hm.insert(k.to_key().into(), v.to_automerge_checked()?);
+        }
+        Ok(Value::Map(hm))
+    }
+
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        map_changed(
+            self,
+            old,
+            |k| old.get(k),
+            |k| self.contains_key(k),
+            path,
+            changed,
+        )
+    }
 }
 
 impl<K, V> ToAutomerge for BTreeMap<K, V>
 where
-    K: ToString,
+    K: MapKey + Ord,
     V: ToAutomerge,
 {
     fn to_automerge(&self) -> Value {
         let mut hm = HashMap::with_capacity(self.len());
         for (k, v) in self {
-            hm.insert(k.to_string().into(), v.to_automerge());
+            hm.insert(k.to_key().into(), v.to_automerge());
         }
         Value::Map(hm)
     }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let mut hm = HashMap::with_capacity(self.len());
+        for (k, v) in self {
+            hm.insert(k.to_key().into(), v.to_automerge_checked()?);
+        }
+        Ok(Value::Map(hm))
+    }
+
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        map_changed(
+            self,
+            old,
+            |k| old.get(k),
+            |k| self.contains_key(k),
+            path,
+            changed,
+        )
+    }
+}
+
+/// Find the changed entries of the map `new`, getting entries from `old` with `get_old` and
+/// checking for them in `new` with `in_new`.
+fn map_changed<'a, K, V>(
+    new: impl IntoIterator<Item = (&'a K, &'a V)>,
+    old: impl IntoIterator<Item = (&'a K, &'a V)>,
+    get_old: impl Fn(&K) -> Option<&'a V>,
+    in_new: impl Fn(&K) -> bool,
+    path: &mut Vec<PathSegment>,
+    changed: &mut ChangedValues,
+) -> Result<(), ToAutomergeError>
+where
+    K: MapKey + 'a,
+    V: ToAutomerge + 'a,
+{
+    for (k, v) in new {
+        path.push(PathSegment::Key(k.to_key()));
+        match get_old(k) {
+            Some(old) => v.to_automerge_changed(old, path, changed)?,
+            None => changed.push(path, Some(v.to_automerge_checked()?)),
+        }
+        path.pop();
+    }
+    for (k, _) in old {
+        if !in_new(k) {
+            path.push(PathSegment::Key(k.to_key()));
+            changed.push(path, None);
+            path.pop();
+        }
+    }
+    Ok(())
 }
 
 impl ToAutomerge for String {
@@ -79,6 +391,69 @@ impl ToAutomerge for String {
     }
 }
 
+impl ToAutomerge for str {
+    fn to_automerge(&self) -> Value {
+        Value::Primitive(Primitive::Str(SmolStr::new(self)))
+    }
+}
+
+/// Paths are stored as a string, so must be valid unicode.
+impl ToAutomerge for Path {
+    fn to_automerge(&self) -> Value {
+        expect_value(self.to_automerge_checked())
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        self.to_str()
+            .map(str::to_automerge)
+            .ok_or_else(|| ToAutomergeError::NotUnicode(self.display().to_string()))
+    }
+}
+
+impl ToAutomerge for PathBuf {
+    fn to_automerge(&self) -> Value {
+        self.as_path().to_automerge()
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        self.as_path().to_automerge_checked()
+    }
+}
+
+macro_rules! display_to_automerge {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        /// Stored as a string in the standard textual format.
+        impl ToAutomerge for $x {
+            fn to_automerge(&self) -> Value {
+                Value::Primitive(Primitive::Str(SmolStr::new(self.to_string())))
+            }
+        })*
+    };
+}
+
+display_to_automerge! {
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+}
+
+/// Orderings are stored as the strings `"Less"`, `"Equal"` and `"Greater"`, like a derived unit
+/// enum.
+impl ToAutomerge for Ordering {
+    fn to_automerge(&self) -> Value {
+        let variant = match self {
+            Self::Less => "Less",
+            Self::Equal => "Equal",
+            Self::Greater => "Greater",
+        };
+        Value::Primitive(Primitive::Str(SmolStr::new(variant)))
+    }
+}
+
 impl ToAutomerge for char {
     fn to_automerge(&self) -> Value {
         Value::Primitive(Primitive::Str(SmolStr::new(self.to_string())))
@@ -89,6 +464,26 @@ impl ToAutomerge for f64 {
     fn to_automerge(&self) -> Value {
         Value::Primitive(Primitive::F64(*self))
     }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        // NaN would never compare equal to itself so would be rewritten on every change
+        if self.is_nan() {
+            Err(ToAutomergeError::NotANumber)
+        } else {
+            Ok(self.to_automerge())
+        }
+    }
+}
+
+/// `f32`s are stored as an `f64`, which holds every `f32` exactly.
+impl ToAutomerge for f32 {
+    fn to_automerge(&self) -> Value {
+        f64::from(*self).to_automerge()
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        f64::from(*self).to_automerge_checked()
+    }
 }
 
 impl ToAutomerge for bool {
@@ -105,15 +500,54 @@ where
         self.as_ref()
             .map_or(Value::Primitive(Primitive::Null), |v| v.to_automerge())
     }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        self.as_ref()
+            .map_or(Ok(Value::Primitive(Primitive::Null)), |v| {
+                v.to_automerge_checked()
+            })
+    }
+
+    fn to_automerge_changed(
+        &self,
+        old: &Self,
+        path: &mut Vec<PathSegment>,
+        changed: &mut ChangedValues,
+    ) -> Result<(), ToAutomergeError> {
+        match (self, old) {
+            (Some(new), Some(old)) => new.to_automerge_changed(old, path, changed),
+            _ => {
+                changed.push(path, Some(self.to_automerge_checked()?));
+                Ok(())
+            }
+        }
+    }
 }
 
-#[cfg(feature = "std")]
-impl ToAutomerge for std::time::SystemTime {
+/// Results are stored as a map with a single `"Ok"` or `"Err"` key, like a derived enum.
+impl<T, E> ToAutomerge for Result<T, E>
+where
+    T: ToAutomerge,
+    E: ToAutomerge,
+{
     fn to_automerge(&self) -> Value {
-        let ts = self
-            .duration_since(Self::UNIX_EPOCH)
-            .expect("time went backwards");
-        Value::Primitive(Primitive::Timestamp(ts.as_secs().try_into().unwrap()))
+        let (variant, value) = match self {
+            Ok(v) => ("Ok", v.to_automerge()),
+            Err(e) => ("Err", e.to_automerge()),
+        };
+        let mut outer = HashMap::with_capacity(1);
+        outer.insert(SmolStr::new(variant), value);
+        Value::Map(outer)
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        let (variant, value) = match self {
+            Ok(v) => ("Ok", v.to_automerge_checked()?),
+            Err(e) => ("Err", e.to_automerge_checked()?),
+        };
+        let mut outer = HashMap::with_capacity(1);
+        outer.insert(SmolStr::new(variant), value);
+        Ok(Value::Map(outer))
     }
 }
 
@@ -140,16 +574,27 @@ impl ToAutomerge for i64 {
     }
 }
 
-impl ToAutomerge for isize {
-    fn to_automerge(&self) -> Value {
-        Value::Primitive(Primitive::Int((*self).try_into().unwrap()))
-    }
+macro_rules! checked_to_automerge {
+    ( $primitive:ident($target:ty): $( $x:ty ),* $(,)? ) => {
+        $(
+        impl ToAutomerge for $x {
+            fn to_automerge(&self) -> Value {
+                expect_value(self.to_automerge_checked())
+            }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                let n = <$target>::try_from(*self).map_err(|_| ToAutomergeError::OutOfRange {
+                    value: self.to_string(),
+                    target_type: stringify!($primitive),
+                })?;
+                Ok(Value::Primitive(Primitive::$primitive(n)))
+            }
+        })*
+    };
 }
 
-impl ToAutomerge for i128 {
-    fn to_automerge(&self) -> Value {
-        Value::Primitive(Primitive::Int((*self).try_into().unwrap()))
-    }
+checked_to_automerge! {
+    Int(i64): isize, i128
 }
 
 macro_rules! as_u64_to_automerge {
@@ -175,16 +620,8 @@ impl ToAutomerge for u64 {
     }
 }
 
-impl ToAutomerge for usize {
-    fn to_automerge(&self) -> Value {
-        Value::Primitive(Primitive::Uint((*self).try_into().unwrap()))
-    }
-}
-
-impl ToAutomerge for u128 {
-    fn to_automerge(&self) -> Value {
-        Value::Primitive(Primitive::Uint((*self).try_into().unwrap()))
-    }
+checked_to_automerge! {
+    Uint(u64): usize, u128
 }
 
 macro_rules! nonzero_to_automerge {
@@ -194,6 +631,10 @@ macro_rules! nonzero_to_automerge {
             fn to_automerge(&self) -> Value {
                 self.get().to_automerge()
             }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                self.get().to_automerge_checked()
+            }
         })*
     };
 }
@@ -213,8 +654,9 @@ nonzero_to_automerge! {
     std::num::NonZeroUsize,
 }
 
+/// Converts from plain JSON, see [`json`](crate::json) for a lossless encoding that keeps text,
+/// bytes, counters and timestamps.
 impl ToAutomerge for serde_json::Value {
-    // TODO: need to convert back to text and bytes somehow
     fn to_automerge(&self) -> Value {
         match self {
             serde_json::Value::Null => automerge::Value::Primitive(Primitive::Null),
@@ -246,17 +688,81 @@ macro_rules! refs {
         $(
         impl<T> ToAutomerge for $x
         where
-            T: ToAutomerge,
+            T: ToAutomerge + ?Sized,
         {
             fn to_automerge(&self) -> Value {
                 (**self).to_automerge()
             }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                (**self).to_automerge_checked()
+            }
+
+            fn to_automerge_changed(
+                &self,
+                old: &Self,
+                path: &mut Vec<PathSegment>,
+                changed: &mut ChangedValues,
+            ) -> Result<(), ToAutomergeError> {
+                (**self).to_automerge_changed(&**old, path, changed)
+            }
         })*
     };
 }
 
 refs! {
+    &T,
     Box<T>,
-    Rc<T>,
-    Arc<T>,
+}
+
+/// Shared pointers are skipped when comparing to an earlier version that points to the same
+/// value, so parts of a document can be shared between versions and only those edited (with
+/// [`Rc::make_mut`] or [`Arc::make_mut`]) are converted again.
+macro_rules! shared {
+    ( $( $x:ident ),* $(,)? ) => {
+        $(
+        impl<T> ToAutomerge for $x<T>
+        where
+            T: ToAutomerge + ?Sized,
+        {
+            fn to_automerge(&self) -> Value {
+                (**self).to_automerge()
+            }
+
+            fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+                (**self).to_automerge_checked()
+            }
+
+            fn to_automerge_changed(
+                &self,
+                old: &Self,
+                path: &mut Vec<PathSegment>,
+                changed: &mut ChangedValues,
+            ) -> Result<(), ToAutomergeError> {
+                if $x::ptr_eq(self, old) {
+                    Ok(())
+                } else {
+                    (**self).to_automerge_changed(&**old, path, changed)
+                }
+            }
+        })*
+    };
+}
+
+shared! {
+    Rc,
+    Arc,
+}
+
+impl<T> ToAutomerge for Cow<'_, T>
+where
+    T: ToAutomerge + ToOwned + ?Sized,
+{
+    fn to_automerge(&self) -> Value {
+        (**self).to_automerge()
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        (**self).to_automerge_checked()
+    }
 }
@@ -0,0 +1,473 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    convert::TryInto,
+    hash::Hash,
+    rc::Rc,
+    sync::Arc,
+};
+
+use automerge::ScalarValue;
+use automergeable_traits::{
+    AsTimestamp, Counter, FromAutomergeError, MapKey, Text, Timestamp, ValueKind,
+};
+
+use crate::ReadView;
+
+/// Decode a value straight from a view of a document.
+///
+/// This reads the same representation that [`FromAutomerge`] reads from an `automerge::Value`,
+/// without building the value first. Use it with a [`View`](crate::View) for the current state
+/// of the document, or a [`HistoricalView`](crate::HistoricalView) for an earlier state.
+///
+/// This is derived alongside the other traits by `#[derive(Automergeable)]` when the
+/// `automergeable-view` feature of `automergeable` is enabled, or on its own by
+/// `#[derive(FromView)]`.
+///
+/// [`FromAutomerge`]: automergeable_traits::FromAutomerge
+pub trait FromView: Sized {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError>;
+}
+
+fn wrong_type<R: ReadView>(view: &R, expected: &str) -> FromAutomergeError {
+    FromAutomergeError::WrongType {
+        found: view.kind(),
+        expected: expected.to_owned(),
+    }
+}
+
+impl FromView for () {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Null) = view.scalar() {
+            Ok(())
+        } else {
+            Err(wrong_type(view, "a primitive null"))
+        }
+    }
+}
+
+impl FromView for bool {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Boolean(b)) = view.scalar() {
+            Ok(b)
+        } else {
+            Err(wrong_type(view, "a primitive boolean"))
+        }
+    }
+}
+
+impl FromView for String {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Str(s)) = view.scalar() {
+            Ok(s.to_string())
+        } else {
+            Err(wrong_type(view, "a primitive string"))
+        }
+    }
+}
+
+macro_rules! str_from_view {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl FromView for $x {
+            fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+                String::from_view(view).map(Self::from)
+            }
+        })*
+    };
+}
+
+str_from_view! {
+    Box<str>,
+    Rc<str>,
+    Arc<str>,
+}
+
+impl FromView for char {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        let s = String::from_view(view)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(wrong_type(view, "a primitive string")),
+        }
+    }
+}
+
+impl FromView for i64 {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Int(i)) = view.scalar() {
+            Ok(i)
+        } else {
+            Err(wrong_type(view, "a primitive int"))
+        }
+    }
+}
+
+impl FromView for u64 {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Uint(u)) = view.scalar() {
+            Ok(u)
+        } else {
+            Err(wrong_type(view, "a primitive uint"))
+        }
+    }
+}
+
+macro_rules! checked_from_view {
+    ( $from:ty => $( $x:ty ),* $(,)? ) => {
+        $(
+        impl FromView for $x {
+            fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+                let n = <$from>::from_view(view)?;
+                n.try_into().map_err(|_| FromAutomergeError::OutOfRange {
+                    value: n.to_string(),
+                    target_type: stringify!($x),
+                })
+            }
+        })*
+    };
+}
+
+checked_from_view! {
+    i64 => i8, i16, i32, isize, i128
+}
+
+checked_from_view! {
+    u64 => u8, u16, u32, usize, u128
+}
+
+impl FromView for f64 {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::F64(f)) = view.scalar() {
+            Ok(f)
+        } else {
+            Err(wrong_type(view, "a primitive f64"))
+        }
+    }
+}
+
+impl FromView for f32 {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        let f = f64::from_view(view)?;
+        // the cast saturates to infinity so check that the value was in range
+        let narrowed = f as Self;
+        if narrowed.is_infinite() && f.is_finite() {
+            Err(FromAutomergeError::OutOfRange {
+                value: f.to_string(),
+                target_type: "f32",
+            })
+        } else {
+            Ok(narrowed)
+        }
+    }
+}
+
+impl FromView for Text {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        view.text()
+            .map(Self::from)
+            .ok_or_else(|| wrong_type(view, "some text"))
+    }
+}
+
+impl FromView for Counter {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Counter(c)) = view.scalar() {
+            Ok(Self::new(i64::from(&c)))
+        } else {
+            Err(wrong_type(view, "a primitive counter"))
+        }
+    }
+}
+
+/// Read points in time from automerge timestamps.
+macro_rules! timestamp_from_view {
+    ( $( $(#[$attr:meta])* $x:ty ),* $(,)? ) => {
+        $(
+        $(#[$attr])*
+        impl FromView for $x {
+            fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+                if let Some(ScalarValue::Timestamp(t)) = view.scalar() {
+                    Self::from_timestamp(t)
+                } else {
+                    Err(wrong_type(view, "a primitive timestamp"))
+                }
+            }
+        })*
+    };
+}
+
+timestamp_from_view! {
+    Timestamp,
+    #[cfg(feature = "std")]
+    std::time::SystemTime,
+}
+
+/// Durations are stored as a number of milliseconds.
+#[cfg(feature = "std")]
+impl FromView for std::time::Duration {
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        u64::from_view(view).map(Self::from_millis)
+    }
+}
+
+impl<T> FromView for Option<T>
+where
+    T: FromView,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        if let Some(ScalarValue::Null) = view.scalar() {
+            Ok(None)
+        } else {
+            Ok(Some(T::from_view(view)?))
+        }
+    }
+}
+
+macro_rules! pointer_from_view {
+    ( $( $x:ident ),* $(,)? ) => {
+        $(
+        impl<T> FromView for $x<T>
+        where
+            T: FromView,
+        {
+            fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+                T::from_view(view).map(Self::new)
+            }
+        })*
+    };
+}
+
+pointer_from_view! {
+    Box,
+    Rc,
+    Arc,
+}
+
+/// Decode the items of a list.
+fn list_from_view<R, T, C>(view: &R) -> Result<C, FromAutomergeError>
+where
+    R: ReadView,
+    T: FromView,
+    C: std::iter::FromIterator<T>,
+{
+    if view.kind() == ValueKind::List {
+        view.items()
+            .iter()
+            .enumerate()
+            .map(|(i, item)| T::from_view(item).map_err(|e| e.at_index(i)))
+            .collect()
+    } else {
+        Err(wrong_type(view, "a list"))
+    }
+}
+
+impl<T> FromView for Vec<T>
+where
+    T: FromView,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        list_from_view(view)
+    }
+}
+
+impl<T> FromView for VecDeque<T>
+where
+    T: FromView,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        list_from_view(view)
+    }
+}
+
+/// Decode the entries of a map, keyed by the [`MapKey`] of each key.
+fn map_from_view<R, K, V, C>(view: &R) -> Result<C, FromAutomergeError>
+where
+    R: ReadView,
+    K: MapKey,
+    V: FromView,
+    C: std::iter::FromIterator<(K, V)>,
+{
+    if view.kind() == ValueKind::Map {
+        view.keys()
+            .into_iter()
+            .map(|k| {
+                let key = K::from_key(&k).map_err(|e| e.at_key(&k))?;
+                // the key came from the map so is always present
+                let value = view.get_key(&k).expect("key from map to have a value");
+                let value = V::from_view(&value).map_err(|e| e.at_key(&k))?;
+                Ok((key, value))
+            })
+            .collect()
+    } else {
+        Err(wrong_type(view, "a map"))
+    }
+}
+
+impl<K, V> FromView for HashMap<K, V>
+where
+    K: MapKey + Eq + Hash,
+    V: FromView,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        map_from_view(view)
+    }
+}
+
+impl<K, V> FromView for BTreeMap<K, V>
+where
+    K: MapKey + Ord,
+    V: FromView,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        map_from_view(view)
+    }
+}
+
+/// Decode the elements of a set, stored as a map from the key of each element to `true`.
+fn set_from_view<R, T, C>(view: &R) -> Result<C, FromAutomergeError>
+where
+    R: ReadView,
+    T: MapKey,
+    C: std::iter::FromIterator<T>,
+{
+    map_from_view::<R, T, bool, Vec<_>>(view)?
+        .into_iter()
+        .map(|(element, present)| {
+            if present {
+                Ok(element)
+            } else {
+                Err(FromAutomergeError::WrongType {
+                    found: ValueKind::Boolean,
+                    expected: "a primitive boolean true".to_owned(),
+                }
+                .at_key(&element.to_key()))
+            }
+        })
+        .collect()
+}
+
+impl<T> FromView for HashSet<T>
+where
+    T: MapKey + Eq + Hash,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        set_from_view(view)
+    }
+}
+
+impl<T> FromView for BTreeSet<T>
+where
+    T: MapKey + Ord,
+{
+    fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+        set_from_view(view)
+    }
+}
+
+macro_rules! tuple_from_view {
+    ( $( $len:literal => ( $( $t:ident $i:tt ),+ ) ),* $(,)? ) => {
+        $(
+        impl<$( $t ),+> FromView for ( $( $t, )+ )
+        where
+            $( $t: FromView, )+
+        {
+            fn from_view<R: ReadView>(view: &R) -> Result<Self, FromAutomergeError> {
+                let items = view.items();
+                if view.kind() != ValueKind::List || items.len() != $len {
+                    return Err(wrong_type(view, concat!("a list of ", $len, " elements")));
+                }
+                Ok(($( $t::from_view(&items[$i]).map_err(|e| e.at_index($i))?, )+))
+            }
+        })*
+    };
+}
+
+tuple_from_view! {
+    1 => (A 0),
+    2 => (A 0, B 1),
+    3 => (A 0, B 1, C 2),
+    4 => (A 0, B 1, C 2, D 3),
+    5 => (A 0, B 1, C 2, D 3, E 4),
+    6 => (A 0, B 1, C 2, D 3, E 4, F 5),
+    7 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    8 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    9 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    10 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    11 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    12 => (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use automerge::{transaction::Transactable, Automerge, ObjType, ROOT};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{automerge_doc, View, ViewableDoc};
+
+    #[test]
+    fn from_view_std_types() {
+        let doc = automerge_doc(json!({
+            "name": "hello",
+            "list": [1, 2, 3],
+            "map": {"a": 1, "b": 2},
+            "maybe": null,
+        }))
+        .unwrap();
+        let root = View::Map(doc.view());
+
+        assert_eq!(
+            String::from_view(&root.get_key("name").unwrap()).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            Vec::<u8>::from_view(&root.get_key("list").unwrap()).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            <(u64, u64, u64)>::from_view(&root.get_key("list").unwrap()).unwrap(),
+            (1, 2, 3)
+        );
+        let mut expected = HashMap::new();
+        expected.insert("a".to_owned(), 1u64);
+        expected.insert("b".to_owned(), 2u64);
+        assert_eq!(
+            HashMap::<String, u64>::from_view(&root.get_key("map").unwrap()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            Option::<String>::from_view(&root.get_key("maybe").unwrap()).unwrap(),
+            None
+        );
+
+        let err = Vec::<String>::from_view(&root.get_key("list").unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at [0]: found a primitive uint but expected a primitive string"
+        );
+    }
+
+    #[test]
+    fn from_view_text_and_history() {
+        let mut doc = Automerge::new();
+        let mut tx = doc.transaction();
+        let text = tx.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        tx.insert(&text, 0, "a").unwrap();
+        tx.commit();
+        let heads = doc.get_heads();
+
+        let mut tx = doc.transaction();
+        tx.insert(&text, 1, "b").unwrap();
+        tx.commit();
+
+        let now = View::Map(doc.view()).get_key("text").unwrap();
+        assert_eq!(Text::from_view(&now).unwrap(), "ab");
+        let then = crate::HistoricalView::Map(doc.view_at(&heads))
+            .get_key("text")
+            .unwrap();
+        assert_eq!(Text::from_view(&then).unwrap(), "a");
+    }
+}
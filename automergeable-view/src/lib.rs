@@ -1,13 +1,17 @@
+mod from_view;
 mod historical;
 mod immutable;
 mod list;
 mod map;
 mod mutable;
 mod mutable_doc;
+mod read_view;
+mod reconcile;
 mod text;
 mod viewable;
 mod viewable_doc;
 
+pub use from_view::FromView;
 pub use historical::HistoricalView;
 pub use immutable::View;
 pub use list::HistoricalListView;
@@ -16,6 +20,8 @@ pub use map::HistoricalMapView;
 pub use map::{MapView, MutableMapView};
 pub use mutable::MutableView;
 pub use mutable_doc::MutableDoc;
+pub use read_view::ReadView;
+pub use reconcile::{Fields, Items, Reconcile, ReconcileError, Slot, Variant};
 pub use text::HistoricalTextView;
 pub use text::{MutableTextView, TextView};
 pub use viewable::Viewable;
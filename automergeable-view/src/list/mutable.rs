@@ -166,6 +166,11 @@ impl<'a, 't> MutableListView<'a, 't> {
         }
     }
 
+    /// Increment the counter at `index` by `by`, which can be negative.
+    pub fn increment(&mut self, index: usize, by: i64) {
+        self.tx.increment(&self.obj, index, by).unwrap()
+    }
+
     pub fn remove(&mut self, index: usize) -> bool {
         if self.get_mut(index).is_some() {
             self.tx.delete(&self.obj, index).unwrap();
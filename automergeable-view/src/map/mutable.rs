@@ -121,6 +121,11 @@ impl<'a, 't> MutableMapView<'a, 't> {
         }
     }
 
+    /// Increment the counter at `key` by `by`, which can be negative.
+    pub fn increment<S: Into<String>>(&mut self, key: S, by: i64) {
+        self.tx.increment(&self.obj, key.into(), by).unwrap()
+    }
+
     /// Remove a value from this map, returning a whether a value was removed or not.
     pub fn remove<S: Into<String>>(&mut self, key: S) -> bool {
         let key = key.into();
@@ -0,0 +1,119 @@
+use automerge::ScalarValue;
+use automergeable_traits::ValueKind;
+
+use crate::{HistoricalView, View, Viewable};
+
+/// A view of a value in a document that typed values can be decoded from.
+///
+/// This is implemented for both [`View`] and [`HistoricalView`], so values can be read from the
+/// document as it is now or as it was at some heads.
+pub trait ReadView: Sized {
+    /// The kind of value being viewed.
+    fn kind(&self) -> ValueKind;
+
+    /// Get the scalar value, if this is one.
+    fn scalar(&self) -> Option<ScalarValue>;
+
+    /// Get the keys of a map, in sorted order, or nothing for other values.
+    fn keys(&self) -> Vec<String>;
+
+    /// Get the value at `key` in a map.
+    fn get_key(&self, key: &str) -> Option<Self>;
+
+    /// Get the items of a list, or nothing for other values.
+    fn items(&self) -> Vec<Self>;
+
+    /// Get the contents of some text as a string.
+    fn text(&self) -> Option<String>;
+}
+
+/// The kind of a scalar value.
+pub(crate) fn scalar_kind(scalar: &ScalarValue) -> ValueKind {
+    match scalar {
+        ScalarValue::Bytes(_) => ValueKind::Bytes,
+        ScalarValue::Str(_) => ValueKind::Str,
+        ScalarValue::Int(_) => ValueKind::Int,
+        ScalarValue::Uint(_) => ValueKind::Uint,
+        ScalarValue::F64(_) => ValueKind::F64,
+        ScalarValue::Counter(_) => ValueKind::Counter,
+        ScalarValue::Timestamp(_) => ValueKind::Timestamp,
+        ScalarValue::Boolean(_) => ValueKind::Boolean,
+        ScalarValue::Null => ValueKind::Null,
+    }
+}
+
+impl<'a, V> ReadView for View<'a, V>
+where
+    V: Viewable,
+{
+    fn kind(&self) -> ValueKind {
+        match self {
+            View::Map(_) => ValueKind::Map,
+            View::List(_) => ValueKind::List,
+            View::Text(_) => ValueKind::Text,
+            View::Scalar(scalar) => scalar_kind(scalar),
+        }
+    }
+
+    fn scalar(&self) -> Option<ScalarValue> {
+        View::scalar(self)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.map()
+            .map(|map| map.keys().collect())
+            .unwrap_or_default()
+    }
+
+    fn get_key(&self, key: &str) -> Option<Self> {
+        self.map().and_then(|map| map.get(key))
+    }
+
+    fn items(&self) -> Vec<Self> {
+        self.list()
+            .map(|list| (0..list.len()).filter_map(|i| list.get(i)).collect())
+            .unwrap_or_default()
+    }
+
+    fn text(&self) -> Option<String> {
+        View::text(self).map(|text| text.as_string())
+    }
+}
+
+impl<'a, 'h, V> ReadView for HistoricalView<'a, 'h, V>
+where
+    V: Viewable,
+{
+    fn kind(&self) -> ValueKind {
+        match self {
+            HistoricalView::Map(_) => ValueKind::Map,
+            HistoricalView::List(_) => ValueKind::List,
+            HistoricalView::Text(_) => ValueKind::Text,
+            HistoricalView::Scalar(scalar) => scalar_kind(scalar),
+        }
+    }
+
+    fn scalar(&self) -> Option<ScalarValue> {
+        HistoricalView::scalar(self)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.map()
+            .map(|map| map.keys().collect())
+            .unwrap_or_default()
+    }
+
+    fn get_key(&self, key: &str) -> Option<Self> {
+        self.map().and_then(|map| map.get(key))
+    }
+
+    fn items(&self) -> Vec<Self> {
+        self.list()
+            .map(|list| (0..list.len()).filter_map(|i| list.get(i)).collect())
+            .unwrap_or_default()
+    }
+
+    fn text(&self) -> Option<String> {
+        HistoricalView::text(self).map(|text| text.as_string())
+    }
+}
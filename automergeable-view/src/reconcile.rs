@@ -0,0 +1,874 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    convert::TryFrom,
+    rc::Rc,
+    sync::Arc,
+};
+
+use automerge::{transaction::Transaction, ObjType, ScalarValue};
+use automergeable_traits::{
+    AsTimestamp, Counter, MapKey, Text, Timestamp, ToAutomergeError, ValueKind,
+};
+use smol_str::SmolStr;
+
+use crate::{
+    read_view::scalar_kind, MutableListView, MutableMapView, MutableTextView, MutableView, View,
+};
+
+/// Write a value into a document, only changing what differs from what is already there.
+///
+/// Scalars are only put if they changed, nested objects are reused when they are already of
+/// the right type, counters are incremented rather than overwritten, and text is spliced.
+///
+/// Derive it with `#[derive(Reconcile)]`.
+///
+/// ```rust,ignore
+/// let mut tx = doc.transaction();
+/// state.reconcile(&mut MutableView::Map(tx.view_mut()))?;
+/// tx.commit();
+/// ```
+pub trait Reconcile {
+    /// Make the value in `slot` match this one.
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError>;
+
+    /// Make the object in `view` match this value.
+    ///
+    /// Only values stored as objects, such as structs, maps, lists and text, can be reconciled
+    /// into one. Scalars can only be reconciled into a slot of their parent.
+    fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+        Err(ReconcileError::WrongType {
+            found: mutable_kind(view),
+            expected: "an entry of a map or list",
+        })
+    }
+
+    /// Whether `current` already holds this value, so reconciling would change nothing.
+    ///
+    /// This is how the unchanged items at the start and end of a list are found, so only the
+    /// items between them are written. The default never matches, leaving items to be reconciled
+    /// by their index.
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        let _ = current;
+        false
+    }
+}
+
+/// A failure writing a value into a document.
+#[derive(thiserror::Error, Debug)]
+pub enum ReconcileError {
+    /// The value couldn't be converted to what it is stored as.
+    #[error(transparent)]
+    Convert(#[from] ToAutomergeError),
+    /// The object being reconciled into doesn't have the type this value is stored as.
+    #[error("can't reconcile {expected} into {found}")]
+    WrongType {
+        /// The kind of object that was found.
+        found: ValueKind,
+        /// What the value is stored as.
+        expected: &'static str,
+    },
+}
+
+fn mutable_kind(view: &MutableView<'_, '_>) -> ValueKind {
+    match view {
+        MutableView::Map(_) => ValueKind::Map,
+        MutableView::List(_) => ValueKind::List,
+        MutableView::Text(_) => ValueKind::Text,
+        MutableView::Scalar(scalar) => scalar_kind(scalar),
+    }
+}
+
+/// Where a value is reconciled to in its parent object.
+#[derive(Debug)]
+pub enum Slot<'s, 'a, 't> {
+    /// The entry for a key in a map.
+    Key(&'s mut MutableMapView<'a, 't>, String),
+    /// An existing item in a list.
+    Index(&'s mut MutableListView<'a, 't>, usize),
+    /// A new item to insert into a list.
+    Insert(&'s mut MutableListView<'a, 't>, usize),
+}
+
+impl<'s, 'a, 't> Slot<'s, 'a, 't> {
+    /// Get the current value in this slot, if there is one.
+    pub fn current(&self) -> Option<View<'_, Transaction<'a>>> {
+        match self {
+            Slot::Key(map, key) => map.get(key.as_str()),
+            Slot::Index(list, index) => list.get(*index),
+            Slot::Insert(..) => None,
+        }
+    }
+
+    /// Store a scalar value, unless it is already there.
+    pub fn put(self, value: ScalarValue) {
+        if matches!(self.current(), Some(View::Scalar(current)) if current == value) {
+            return;
+        }
+        match self {
+            Slot::Key(map, key) => map.insert(key, value),
+            Slot::Index(list, index) => list.set(index, value),
+            Slot::Insert(list, index) => list.insert(index, value),
+        }
+    }
+
+    /// Store a counter, incrementing any counter already there to reach `value`.
+    pub fn counter(self, value: i64) {
+        let current = match self.current() {
+            Some(View::Scalar(ScalarValue::Counter(c))) => Some(i64::from(&c)),
+            _ => None,
+        };
+        match (current, self) {
+            (Some(current), _) if current == value => {}
+            (Some(current), Slot::Key(map, key)) => map.increment(key, value - current),
+            (Some(current), Slot::Index(list, index)) => list.increment(index, value - current),
+            (_, slot) => slot.put(ScalarValue::counter(value)),
+        }
+    }
+
+    /// Get the map in this slot, creating it if there isn't one.
+    pub fn map(self) -> MutableView<'a, 's> {
+        self.object(ObjType::Map)
+    }
+
+    /// Get the list in this slot, creating it if there isn't one.
+    pub fn list(self) -> MutableView<'a, 's> {
+        self.object(ObjType::List)
+    }
+
+    /// Get the text in this slot, creating it if there isn't any.
+    pub fn text(self) -> MutableView<'a, 's> {
+        self.object(ObjType::Text)
+    }
+
+    fn object(self, obj_type: ObjType) -> MutableView<'a, 's> {
+        let exists = matches!(
+            (self.current(), obj_type),
+            (Some(View::Map(_)), ObjType::Map)
+                | (Some(View::List(_)), ObjType::List)
+                | (Some(View::Text(_)), ObjType::Text)
+        );
+        match self {
+            Slot::Key(map, key) => {
+                if exists {
+                    map.get_mut(key).expect("existing object to be in the map")
+                } else {
+                    map.insert_object(key, obj_type)
+                }
+            }
+            Slot::Index(list, index) => {
+                if exists {
+                    list.get_mut(index)
+                        .expect("existing object to be in the list")
+                } else {
+                    list.set_object(index, obj_type)
+                }
+            }
+            Slot::Insert(list, index) => list.insert_object(index, obj_type),
+        }
+    }
+}
+
+fn expect_map<'v, 'a, 't>(
+    view: &'v mut MutableView<'a, 't>,
+) -> Result<&'v mut MutableMapView<'a, 't>, ReconcileError> {
+    let found = mutable_kind(view);
+    view.map_mut().ok_or(ReconcileError::WrongType {
+        found,
+        expected: "a map",
+    })
+}
+
+fn expect_list<'v, 'a, 't>(
+    view: &'v mut MutableView<'a, 't>,
+) -> Result<&'v mut MutableListView<'a, 't>, ReconcileError> {
+    let found = mutable_kind(view);
+    view.list_mut().ok_or(ReconcileError::WrongType {
+        found,
+        expected: "a list",
+    })
+}
+
+/// Whether `current` is the scalar `value`.
+fn scalar_matches(current: &View<'_, Transaction<'_>>, value: ScalarValue) -> bool {
+    matches!(current, View::Scalar(current) if *current == value)
+}
+
+/// Whether the item at `index` of the list already holds `item`.
+fn item_matches<T>(list: &MutableListView<'_, '_>, index: usize, item: &T) -> bool
+where
+    T: Reconcile,
+{
+    list.get(index)
+        .map_or(false, |current| item.matches(&current))
+}
+
+/// Reconcile items into a list, skipping the items that match at the start and end of it.
+///
+/// The items between are updated in place, inserting any new ones after them and deleting any
+/// left over, so inserting or removing items only writes those items.
+fn reconcile_items<I>(list: &mut MutableListView<'_, '_>, items: I) -> Result<(), ReconcileError>
+where
+    I: IntoIterator,
+    I::Item: Reconcile,
+{
+    let items = items.into_iter().collect::<Vec<_>>();
+    let existing = list.len();
+    let prefix = (0..items.len().min(existing))
+        .take_while(|&i| item_matches(list, i, &items[i]))
+        .count();
+    let suffix = (0..(items.len() - prefix).min(existing - prefix))
+        .take_while(|&i| item_matches(list, existing - 1 - i, &items[items.len() - 1 - i]))
+        .count();
+    let new_middle = &items[prefix..items.len() - suffix];
+    let old_middle = existing - prefix - suffix;
+    for (i, item) in new_middle.iter().enumerate() {
+        if i < old_middle {
+            item.reconcile_slot(Slot::Index(list, prefix + i))?;
+        } else {
+            item.reconcile_slot(Slot::Insert(list, prefix + i))?;
+        }
+    }
+    // reverse so delete from the end
+    for index in (prefix + new_middle.len()..prefix + old_middle).rev() {
+        list.remove(index);
+    }
+    Ok(())
+}
+
+/// Whether `current` is a list of exactly these items.
+fn items_match<I>(current: &View<'_, Transaction<'_>>, items: I) -> bool
+where
+    I: IntoIterator,
+    I::IntoIter: ExactSizeIterator,
+    I::Item: Reconcile,
+{
+    let items = items.into_iter();
+    match current {
+        View::List(list) => {
+            list.len() == items.len()
+                && items
+                    .zip(list.iter())
+                    .all(|(item, current)| item.matches(&current))
+        }
+        _ => false,
+    }
+}
+
+/// Reconcile entries into a map, deleting any keys that aren't among them.
+fn reconcile_entries<'e, I, V>(
+    map: &mut MutableMapView<'_, '_>,
+    entries: I,
+) -> Result<(), ReconcileError>
+where
+    I: IntoIterator<Item = (String, &'e V)>,
+    V: Reconcile + 'e,
+{
+    let mut keys = HashSet::new();
+    for (key, value) in entries {
+        value.reconcile_slot(Slot::Key(map, key.clone()))?;
+        keys.insert(key);
+    }
+    let stale = map
+        .keys()
+        .filter(|key| !keys.contains(key))
+        .collect::<Vec<_>>();
+    for key in stale {
+        map.remove(key);
+    }
+    Ok(())
+}
+
+/// Whether `current` is a map of exactly these entries.
+fn entries_match<'e, I, V>(current: &View<'_, Transaction<'_>>, entries: I) -> bool
+where
+    I: IntoIterator<Item = (String, &'e V)>,
+    I::IntoIter: ExactSizeIterator,
+    V: Reconcile + 'e,
+{
+    let mut entries = entries.into_iter();
+    match current {
+        View::Map(map) => {
+            map.len() == entries.len()
+                && entries.all(|(key, value)| {
+                    map.get(key)
+                        .map_or(false, |current| value.matches(&current))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Splice the graphemes between the common prefix and suffix of the current text.
+fn reconcile_text(text: &mut MutableTextView<'_, '_>, graphemes: &[SmolStr]) {
+    let old = text.iter();
+    let prefix = old
+        .iter()
+        .zip(graphemes)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(graphemes[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    for _ in prefix..old.len() - suffix {
+        text.remove(prefix);
+    }
+    for (i, grapheme) in graphemes[prefix..graphemes.len() - suffix]
+        .iter()
+        .enumerate()
+    {
+        text.insert(prefix + i, grapheme.as_str());
+    }
+}
+
+/// Named fields reconciled as a map, as structs are stored.
+///
+/// Keys in the map that aren't fields are left alone, as other versions of the type may use
+/// them.
+#[derive(Clone, Copy)]
+pub struct Fields<'r>(pub &'r [(&'r str, &'r dyn Reconcile)]);
+
+impl Reconcile for Fields<'_> {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.reconcile(&mut slot.map())
+    }
+
+    fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+        let map = expect_map(view)?;
+        for (key, value) in self.0 {
+            value.reconcile_slot(Slot::Key(map, (*key).to_owned()))?;
+        }
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        match current {
+            View::Map(map) => self.0.iter().all(|(key, value)| {
+                map.get(*key)
+                    .map_or(false, |current| value.matches(&current))
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Values reconciled as the items of a list, as tuples are stored.
+#[derive(Clone, Copy)]
+pub struct Items<'r>(pub &'r [&'r dyn Reconcile]);
+
+impl Reconcile for Items<'_> {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.reconcile(&mut slot.list())
+    }
+
+    fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+        reconcile_items(expect_list(view)?, self.0)
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        items_match(current, self.0)
+    }
+}
+
+/// A value reconciled as a map with a single key, as enum variants with fields are stored.
+#[derive(Clone, Copy)]
+pub struct Variant<'r>(pub &'r str, pub &'r dyn Reconcile);
+
+impl Reconcile for Variant<'_> {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.reconcile(&mut slot.map())
+    }
+
+    fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+        reconcile_entries(
+            expect_map(view)?,
+            std::iter::once((self.0.to_owned(), &self.1)),
+        )
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        entries_match(current, std::iter::once((self.0.to_owned(), &self.1)))
+    }
+}
+
+macro_rules! refs_reconcile {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl<T> Reconcile for $x
+        where
+            T: Reconcile + ?Sized,
+        {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                (**self).reconcile_slot(slot)
+            }
+
+            fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+                (**self).reconcile(view)
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                (**self).matches(current)
+            }
+        })*
+    };
+}
+
+refs_reconcile! {
+    &T,
+    Box<T>,
+    Rc<T>,
+    Arc<T>,
+}
+
+impl Reconcile for () {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        slot.put(ScalarValue::Null);
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        scalar_matches(current, ScalarValue::Null)
+    }
+}
+
+impl Reconcile for bool {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        slot.put(ScalarValue::Boolean(*self));
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        scalar_matches(current, ScalarValue::Boolean(*self))
+    }
+}
+
+impl Reconcile for str {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        slot.put(ScalarValue::Str(SmolStr::new(self)));
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        scalar_matches(current, ScalarValue::Str(SmolStr::new(self)))
+    }
+}
+
+impl Reconcile for String {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.as_str().reconcile_slot(slot)
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        self.as_str().matches(current)
+    }
+}
+
+impl Reconcile for char {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.encode_utf8(&mut [0; 4]).reconcile_slot(slot)
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        self.encode_utf8(&mut [0; 4]).matches(current)
+    }
+}
+
+macro_rules! scalar_reconcile {
+    ( $variant:ident($target:ty): $( $x:ty ),* $(,)? ) => {
+        $(
+        impl Reconcile for $x {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                let n = <$target>::try_from(*self).map_err(|_| ToAutomergeError::OutOfRange {
+                    value: self.to_string(),
+                    target_type: stringify!($variant),
+                })?;
+                slot.put(ScalarValue::$variant(n));
+                Ok(())
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                <$target>::try_from(*self)
+                    .map_or(false, |n| scalar_matches(current, ScalarValue::$variant(n)))
+            }
+        })*
+    };
+}
+
+scalar_reconcile! {
+    Int(i64): i8, i16, i32, i64, isize, i128
+}
+
+scalar_reconcile! {
+    Uint(u64): u8, u16, u32, u64, usize, u128
+}
+
+impl Reconcile for f64 {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        // NaN would never compare equal to itself so would be rewritten on every change
+        if self.is_nan() {
+            return Err(ToAutomergeError::NotANumber.into());
+        }
+        slot.put(ScalarValue::F64(*self));
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        scalar_matches(current, ScalarValue::F64(*self))
+    }
+}
+
+/// `f32`s are stored as an `f64`, which holds every `f32` exactly.
+impl Reconcile for f32 {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        f64::from(*self).reconcile_slot(slot)
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        f64::from(*self).matches(current)
+    }
+}
+
+impl Reconcile for Text {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        self.reconcile(&mut slot.text())
+    }
+
+    fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+        let found = mutable_kind(view);
+        let text = view.text_mut().ok_or(ReconcileError::WrongType {
+            found,
+            expected: "some text",
+        })?;
+        reconcile_text(text, self.graphemes());
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        match current {
+            View::Text(text) => {
+                text.len() == self.len()
+                    && text
+                        .iter()
+                        .zip(self.graphemes())
+                        .all(|(current, grapheme)| current == *grapheme)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Reconcile for Counter {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        slot.counter(self.value());
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        matches!(current, View::Scalar(ScalarValue::Counter(c)) if i64::from(c) == self.value())
+    }
+}
+
+/// Store points in time as automerge timestamps.
+macro_rules! timestamp_reconcile {
+    ( $( $(#[$attr:meta])* $x:ty ),* $(,)? ) => {
+        $(
+        $(#[$attr])*
+        impl Reconcile for $x {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                slot.put(ScalarValue::Timestamp(self.to_timestamp()?));
+                Ok(())
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                self.to_timestamp()
+                    .map_or(false, |t| scalar_matches(current, ScalarValue::Timestamp(t)))
+            }
+        })*
+    };
+}
+
+timestamp_reconcile! {
+    Timestamp,
+    #[cfg(feature = "std")]
+    std::time::SystemTime,
+}
+
+/// Durations are stored as a number of milliseconds.
+#[cfg(feature = "std")]
+impl Reconcile for std::time::Duration {
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        let millis = u64::try_from(self.as_millis()).map_err(|_| ToAutomergeError::OutOfRange {
+            value: format!("{:?}", self),
+            target_type: "Uint",
+        })?;
+        slot.put(ScalarValue::Uint(millis));
+        Ok(())
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        u64::try_from(self.as_millis()).map_or(false, |millis| {
+            scalar_matches(current, ScalarValue::Uint(millis))
+        })
+    }
+}
+
+impl<T> Reconcile for Option<T>
+where
+    T: Reconcile,
+{
+    fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+        match self {
+            Some(v) => v.reconcile_slot(slot),
+            None => ().reconcile_slot(slot),
+        }
+    }
+
+    fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+        match self {
+            Some(v) => v.matches(current),
+            None => ().matches(current),
+        }
+    }
+}
+
+macro_rules! list_reconcile {
+    ( $( $x:ty ),* $(,)? ) => {
+        $(
+        impl<T> Reconcile for $x
+        where
+            T: Reconcile,
+        {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                self.reconcile(&mut slot.list())
+            }
+
+            fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+                reconcile_items(expect_list(view)?, self.iter())
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                items_match(current, self.iter())
+            }
+        })*
+    };
+}
+
+list_reconcile! {
+    [T],
+    Vec<T>,
+    VecDeque<T>,
+}
+
+macro_rules! map_reconcile {
+    ( $( $x:ident ),* $(,)? ) => {
+        $(
+        /// Maps are keyed by the [`MapKey`] of each key, deleting entries for keys no longer in
+        /// the map.
+        impl<K, V> Reconcile for $x<K, V>
+        where
+            K: MapKey,
+            V: Reconcile,
+        {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                self.reconcile(&mut slot.map())
+            }
+
+            fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+                reconcile_entries(expect_map(view)?, self.iter().map(|(k, v)| (k.to_key(), v)))
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                entries_match(current, self.iter().map(|(k, v)| (k.to_key(), v)))
+            }
+        })*
+    };
+}
+
+map_reconcile! {
+    HashMap,
+    BTreeMap,
+}
+
+macro_rules! set_reconcile {
+    ( $( $x:ident ),* $(,)? ) => {
+        $(
+        /// Sets are stored as a map from the [`MapKey`] of each element to `true`.
+        impl<T> Reconcile for $x<T>
+        where
+            T: MapKey,
+        {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                self.reconcile(&mut slot.map())
+            }
+
+            fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+                reconcile_entries(expect_map(view)?, self.iter().map(|e| (e.to_key(), &true)))
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                entries_match(current, self.iter().map(|e| (e.to_key(), &true)))
+            }
+        })*
+    };
+}
+
+set_reconcile! {
+    HashSet,
+    BTreeSet,
+}
+
+macro_rules! tuple_reconcile {
+    ( $( ( $( $t:ident $i:tt ),+ ) ),* $(,)? ) => {
+        $(
+        /// Tuples are stored as a list of their fields.
+        impl<$( $t ),+> Reconcile for ( $( $t, )+ )
+        where
+            $( $t: Reconcile, )+
+        {
+            fn reconcile_slot(&self, slot: Slot<'_, '_, '_>) -> Result<(), ReconcileError> {
+                self.reconcile(&mut slot.list())
+            }
+
+            fn reconcile(&self, view: &mut MutableView<'_, '_>) -> Result<(), ReconcileError> {
+                Items(&[$( &self.$i as &dyn Reconcile ),+]).reconcile(view)
+            }
+
+            fn matches(&self, current: &View<'_, Transaction<'_>>) -> bool {
+                Items(&[$( &self.$i as &dyn Reconcile ),+]).matches(current)
+            }
+        })*
+    };
+}
+
+tuple_reconcile! {
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+    (A 0, B 1, C 2, D 3, E 4),
+    (A 0, B 1, C 2, D 3, E 4, F 5),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use automerge::{transaction::Transactable, Automerge};
+
+    use super::*;
+    use crate::{FromView, MutableDoc, ReadView, ViewableDoc};
+
+    #[test]
+    fn reconcile_minimal_changes() {
+        let mut doc = Automerge::new();
+        let mut state = HashMap::new();
+        state.insert("list".to_owned(), vec![1u64, 2, 3]);
+        state.insert("other".to_owned(), vec![4]);
+
+        let mut tx = doc.transaction();
+        state
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        tx.commit();
+        assert_eq!(
+            HashMap::<String, Vec<u64>>::from_view(&View::Map(doc.view())).unwrap(),
+            state
+        );
+
+        // nothing changed so nothing is written
+        let mut tx = doc.transaction();
+        state
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        assert_eq!(tx.pending_ops(), 0);
+        tx.rollback();
+
+        state.get_mut("list").unwrap().truncate(1);
+        state.remove("other");
+        let mut tx = doc.transaction();
+        state
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        // two removals from the list and one from the map
+        assert_eq!(tx.pending_ops(), 3);
+        tx.commit();
+        assert_eq!(
+            HashMap::<String, Vec<u64>>::from_view(&View::Map(doc.view())).unwrap(),
+            state
+        );
+    }
+
+    #[test]
+    fn reconcile_list_middle() {
+        let mut doc = Automerge::new();
+        let mut list = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let mut tx = doc.transaction();
+        Fields(&[("list", &list as &dyn Reconcile)])
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        tx.commit();
+
+        // only the new item is written, rather than every item after it
+        list.insert(0, "z".to_owned());
+        let mut tx = doc.transaction();
+        Fields(&[("list", &list as &dyn Reconcile)])
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        assert_eq!(tx.pending_ops(), 1);
+        tx.commit();
+
+        list.remove(2);
+        let mut tx = doc.transaction();
+        Fields(&[("list", &list as &dyn Reconcile)])
+            .reconcile(&mut MutableView::Map(tx.view_mut()))
+            .unwrap();
+        assert_eq!(tx.pending_ops(), 1);
+        tx.commit();
+
+        let root = View::Map(doc.view());
+        assert_eq!(
+            Vec::<String>::from_view(&root.get_key("list").unwrap()).unwrap(),
+            list
+        );
+    }
+
+    #[test]
+    fn reconcile_counters_and_text() {
+        let mut doc = Automerge::new();
+        let mut tx = doc.transaction();
+        Fields(&[
+            ("count", &Counter::new(1) as &dyn Reconcile),
+            ("text", &Text::from("hello")),
+        ])
+        .reconcile(&mut MutableView::Map(tx.view_mut()))
+        .unwrap();
+        tx.commit();
+
+        let mut tx = doc.transaction();
+        Fields(&[
+            ("count", &Counter::new(3) as &dyn Reconcile),
+            ("text", &Text::from("help")),
+        ])
+        .reconcile(&mut MutableView::Map(tx.view_mut()))
+        .unwrap();
+        tx.commit();
+
+        let root = View::Map(doc.view());
+        assert_eq!(
+            Counter::from_view(&root.get_key("count").unwrap()).unwrap(),
+            Counter::new(3)
+        );
+        assert_eq!(
+            Text::from_view(&root.get_key("text").unwrap()).unwrap(),
+            "help"
+        );
+    }
+}
@@ -1,10 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use automerge::Frontend;
-use automergeable::Document;
+use automergeable::{Automergeable, Document};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
-fn bench_single(mut document: Document<HashMap<String, String>, Frontend>, limit: usize) {
+fn bench_single(document: &mut Document<HashMap<String, String>, Frontend>, limit: usize) {
     black_box(
         document
             .change::<_, _, std::convert::Infallible>(|doc| {
@@ -35,7 +35,7 @@ fn bench_changes_single(c: &mut Criterion) {
         c.bench_function(&format!("change with {} entries, single change", i), |b| {
             b.iter_batched(
                 || Document::<HashMap<String, String>, Frontend>::new(Frontend::new()),
-                |doc| bench_single(doc, i),
+                |mut doc| bench_single(&mut doc, i),
                 criterion::BatchSize::SmallInput,
             )
         });
@@ -54,5 +54,82 @@ fn bench_changes_many(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, bench_changes_single, bench_changes_many);
+fn bench_changes_large_document(c: &mut Criterion) {
+    for &i in &[100, 1000, 10000] {
+        c.bench_function(
+            &format!("single edit to document with {} entries", i),
+            |b| {
+                b.iter_batched(
+                    || {
+                        let mut document =
+                            Document::<HashMap<String, String>, Frontend>::new(Frontend::new());
+                        bench_single(&mut document, i);
+                        document
+                    },
+                    |mut document| {
+                        black_box(
+                            document
+                                .change::<_, _, std::convert::Infallible>(|doc| {
+                                    doc.insert("0".to_owned(), "changed".to_owned());
+                                    Ok(())
+                                })
+                                .unwrap(),
+                        )
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+}
+
+#[derive(Automergeable, Debug, Default, Clone)]
+struct Shared {
+    entries: Arc<HashMap<String, String>>,
+    edits: u64,
+}
+
+fn bench_changes_shared_document(c: &mut Criterion) {
+    for &i in &[100, 1000, 10000] {
+        c.bench_function(
+            &format!("single edit beside shared document with {} entries", i),
+            |b| {
+                b.iter_batched(
+                    || {
+                        let mut document = Document::<Shared, Frontend>::new(Frontend::new());
+                        document
+                            .change::<_, _, std::convert::Infallible>(|doc| {
+                                let entries = Arc::make_mut(&mut doc.entries);
+                                for i in 0..i {
+                                    entries.insert(i.to_string(), i.to_string());
+                                }
+                                Ok(())
+                            })
+                            .unwrap();
+                        document
+                    },
+                    |mut document| {
+                        black_box(
+                            document
+                                .change::<_, _, std::convert::Infallible>(|doc| {
+                                    doc.edits += 1;
+                                    Ok(())
+                                })
+                                .unwrap(),
+                        )
+                    },
+                    criterion::BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_changes_single,
+    bench_changes_many,
+    bench_changes_large_document,
+    bench_changes_shared_document
+);
 criterion_main!(benches);
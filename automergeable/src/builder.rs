@@ -0,0 +1,254 @@
+use automerge::{Backend, Frontend};
+
+use crate::{
+    document::Timestamper, ChangeBudget, DiffOptions, Document, DocumentChangeError,
+    FromAutomergeError, RootAutomergeable,
+};
+
+/// The namespace that [`DocumentBuilder::actor_id_from`] derives actor ids in.
+const ACTOR_NAMESPACE: uuid::Uuid =
+    uuid::Uuid::from_u128(0x9232_ca08_05a1_4d50_b4f5_63f8_65db_04e4);
+
+/// An error from building a [`Document`].
+#[derive(Debug, thiserror::Error)]
+pub enum DocumentBuildError {
+    /// The value in the document couldn't be decoded and there was no way to recover.
+    #[error(transparent)]
+    FromError(#[from] FromAutomergeError),
+    /// The initial value couldn't be written to the document.
+    #[error(transparent)]
+    InitialValue(#[from] DocumentChangeError),
+}
+
+/// A builder for configuring and creating [`Document`]s.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use automergeable::DocumentBuilder;
+/// let (doc, _) = DocumentBuilder::<HashMap<String, String>>::new()
+///     .actor_id_from("user-1234")
+///     .without_timestamps()
+///     .build()
+///     .unwrap();
+/// assert!(doc.get().is_empty());
+/// ```
+pub struct DocumentBuilder<T> {
+    actor_id: Option<uuid::Uuid>,
+    timestamper: Option<Box<dyn Fn() -> Option<i64>>>,
+    budget: ChangeBudget,
+    diff_options: DiffOptions,
+    recovery: Option<Box<dyn FnOnce() -> T>>,
+    initial_value: Option<T>,
+    backend: Option<Backend>,
+}
+
+impl<T> Default for DocumentBuilder<T> {
+    fn default() -> Self {
+        Self {
+            actor_id: None,
+            timestamper: None,
+            budget: ChangeBudget::default(),
+            diff_options: DiffOptions::default(),
+            recovery: None,
+            initial_value: None,
+            backend: None,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for DocumentBuilder<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DocumentBuilder")
+            .field("actor_id", &self.actor_id)
+            .field("budget", &self.budget)
+            .field("diff_options", &self.diff_options)
+            .finish()
+    }
+}
+
+impl<T> DocumentBuilder<T>
+where
+    T: RootAutomergeable,
+{
+    /// Create a new builder with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use the given actor id for the document.
+    pub const fn actor_id(mut self, actor_id: uuid::Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    /// Derive the actor id deterministically from some identifier, such as a user or device id.
+    ///
+    /// This is a version 5 UUID of the identifier. The same identifier always gives the same actor
+    /// id so it must only be used by one replica at a time, otherwise their changes will clash.
+    pub fn actor_id_from(self, id: &str) -> Self {
+        self.actor_id(uuid::Uuid::new_v5(&ACTOR_NAMESPACE, id.as_bytes()))
+    }
+
+    /// Use the given function for the timestamps of changes.
+    pub fn timestamper<G>(mut self, timestamper: G) -> Self
+    where
+        G: Fn() -> Option<i64> + 'static,
+    {
+        self.timestamper = Some(Box::new(timestamper));
+        self
+    }
+
+    /// Don't record timestamps on changes, such as for deterministic tests or targets without a
+    /// clock.
+    pub fn without_timestamps(self) -> Self {
+        self.timestamper(|| None)
+    }
+
+    /// Limit the size of each change made to the document.
+    pub const fn change_budget(mut self, budget: ChangeBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Set how the typed value is diffed to find the changes it makes.
+    pub const fn diff_options(mut self, options: DiffOptions) -> Self {
+        self.diff_options = options;
+        self
+    }
+
+    /// Use the value from `recover` if the document's value can't be decoded.
+    ///
+    /// The next change then overwrites the undecodable parts of the document.
+    pub fn recover_with<G>(mut self, recover: G) -> Self
+    where
+        G: FnOnce() -> T + 'static,
+    {
+        self.recovery = Some(Box::new(recover));
+        self
+    }
+
+    /// Use the default value if the document's value can't be decoded.
+    pub fn recover_with_default(self) -> Self
+    where
+        T: Default + 'static,
+    {
+        self.recover_with(T::default)
+    }
+
+    /// Write the given value into the document once it is built.
+    pub fn initial_value(mut self, value: T) -> Self {
+        self.initial_value = Some(value);
+        self
+    }
+
+    /// Keep an in-memory backend in the document, see [`Document::with_backend`].
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Build the document.
+    ///
+    /// If an initial value was given it is written in a first change, which is returned to be
+    /// sent to the backend and other peers. With a backend it has already been applied to it.
+    pub fn build(
+        self,
+    ) -> Result<(Document<T, Frontend>, Option<automerge_protocol::Change>), DocumentBuildError>
+    where
+        T: Clone,
+    {
+        // shared so the document can give it to the frontend again when resetting it
+        let timestamper = self.timestamper.map(Timestamper::new);
+        let frontend = match (timestamper.as_ref().map(Timestamper::boxed), self.actor_id) {
+            (None, None) => Frontend::new(),
+            (None, Some(actor_id)) => Frontend::new_with_actor_id(actor_id),
+            (Some(timestamper), None) => Frontend::new_with_timestamper(timestamper),
+            (Some(timestamper), Some(actor_id)) => {
+                Frontend::new_with_timestamper_and_actor_id(timestamper, actor_id)
+            }
+        };
+        let recovery = self.recovery;
+        let mut document = Document::load(frontend, self.backend, |e| {
+            recovery.map(|recover| recover()).ok_or(e)
+        })?;
+        if let Some(timestamper) = timestamper {
+            document.set_timestamper(timestamper);
+        }
+        document.set_change_budget(self.budget);
+        document.set_diff_options(self.diff_options);
+        let change = match self.initial_value {
+            Some(initial_value) => {
+                document
+                    .change::<_, _, std::convert::Infallible>(|value| {
+                        *value = initial_value;
+                        Ok(())
+                    })?
+                    .1
+            }
+            None => None,
+        };
+        Ok((document, change))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_from_the_same_user_share_an_actor() {
+        let actor_of = |id: &str| {
+            let (_, change) = DocumentBuilder::<std::collections::HashMap<String, String>>::new()
+                .actor_id_from(id)
+                .initial_value(maplit::hashmap! {"a".to_owned() => "b".to_owned()})
+                .build()
+                .unwrap();
+            change.unwrap().actor_id
+        };
+
+        let actor = actor_of("user-1");
+        assert_eq!(actor, actor_of("user-1"));
+        assert_ne!(actor, actor_of("user-2"));
+    }
+
+    #[test]
+    fn build_with_initial_value() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            name: String,
+        }
+
+        let (doc, change) = DocumentBuilder::new()
+            .actor_id_from("user-1")
+            .without_timestamps()
+            .backend(Backend::new())
+            .initial_value(A {
+                name: "initial".to_owned(),
+            })
+            .build()
+            .unwrap();
+        assert_eq!(doc.get().name, "initial");
+        assert!(change.is_some());
+        assert_eq!(doc.pending_changes().len(), 0);
+    }
+
+    #[test]
+    fn reset_keeps_the_timestamper() {
+        let (mut doc, _) = DocumentBuilder::<std::collections::HashMap<String, String>>::new()
+            .timestamper(|| Some(42))
+            .backend(Backend::new())
+            .build()
+            .unwrap();
+        let patch = doc.backend().unwrap().get_patch().unwrap();
+        doc.reset(patch).unwrap();
+
+        let (_, change) = doc
+            .change::<_, _, std::convert::Infallible>(|value| {
+                value.insert("a".to_owned(), "b".to_owned());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(change.unwrap().time, 42);
+    }
+}
@@ -1,6 +1,97 @@
 use std::convert::TryInto;
 
 use automerge::{InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge_frontend::LocalOperation;
+use automergeable_traits::{ChangedValues, PathSegment, TextSplice, TextSplices};
+use smol_str::SmolStr;
+
+/// A summary of a set of [`LocalChange`]s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiffReport {
+    /// The number of values set.
+    pub sets: usize,
+    /// The number of values inserted into sequences.
+    pub inserts: usize,
+    /// The number of values deleted.
+    pub deletes: usize,
+    /// The number of counter increments.
+    pub increments: usize,
+    /// The number of operations the changes will produce, including those for nested objects.
+    pub operations: usize,
+    /// An estimate of the number of bytes of values the changes will carry.
+    pub bytes: usize,
+}
+
+impl DiffReport {
+    /// Summarise the given changes.
+    pub fn new(changes: &[LocalChange]) -> Self {
+        let mut report = Self::default();
+        for change in changes {
+            match change.operation() {
+                LocalOperation::Set(value) => {
+                    report.sets += 1;
+                    report.add_value(value);
+                }
+                LocalOperation::Insert(value) => {
+                    report.inserts += 1;
+                    report.add_value(value);
+                }
+                LocalOperation::Delete => {
+                    report.deletes += 1;
+                    report.operations += 1;
+                }
+                LocalOperation::Increment(_) => {
+                    report.increments += 1;
+                    report.operations += 1;
+                    report.bytes += 8;
+                }
+            }
+        }
+        report
+    }
+
+    /// Whether there are no changes in this report.
+    pub const fn is_empty(&self) -> bool {
+        self.operations == 0
+    }
+
+    fn add_value(&mut self, value: &Value) {
+        self.operations += 1;
+        match value {
+            Value::Map(map) | Value::Table(map) => {
+                for (k, v) in map {
+                    self.bytes += k.len();
+                    self.add_value(v)
+                }
+            }
+            Value::List(vec) => {
+                for v in vec {
+                    self.add_value(v)
+                }
+            }
+            Value::Text(graphemes) => {
+                self.operations += graphemes.len();
+                self.bytes += graphemes.iter().map(|g| g.len()).sum::<usize>();
+            }
+            Value::Primitive(Primitive::Str(s)) => self.bytes += s.len(),
+            Value::Primitive(Primitive::Bytes(b)) => self.bytes += b.len(),
+            Value::Primitive(Primitive::Boolean(_)) | Value::Primitive(Primitive::Null) => {
+                self.bytes += 1
+            }
+            Value::Primitive(_) => self.bytes += 8,
+        }
+    }
+}
+
+/// Options for how the changes between values are found.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Skip the items that are the same at the start and end of lists, so inserting or removing
+    /// items becomes just those inserts or deletes rather than setting every following item.
+    ///
+    /// Items are otherwise compared by index.
+    pub match_list_ends: bool,
+}
 
 /// Calculate the [`LocalChange`]s between the two values.
 ///
@@ -14,6 +105,151 @@ pub fn diff_with_path(
     new: Option<&Value>,
     old: Option<&Value>,
     path: Path,
+) -> Result<Vec<LocalChange>, InvalidChangeRequest> {
+    diff_with_options(new, old, path, DiffOptions::default())
+}
+
+/// Calculate the [`LocalChange`]s between the two values that start from the given path, with
+/// the given options.
+pub fn diff_with_options(
+    new: Option<&Value>,
+    old: Option<&Value>,
+    path: Path,
+    options: DiffOptions,
+) -> Result<Vec<LocalChange>, InvalidChangeRequest> {
+    diff_inner(
+        new,
+        old,
+        path,
+        &mut Vec::new(),
+        options,
+        &TextSplices::default(),
+    )
+}
+
+/// Calculate the [`LocalChange`]s between the two root values, with the given options.
+///
+/// Texts that have `splices` recorded for them become exactly those edits, rather than the ones
+/// found by comparing the old and new text.
+pub fn diff_with_splices(
+    new: &Value,
+    old: &Value,
+    options: DiffOptions,
+    splices: &TextSplices,
+) -> Result<Vec<LocalChange>, InvalidChangeRequest> {
+    diff_inner(
+        Some(new),
+        Some(old),
+        Path::root(),
+        &mut Vec::new(),
+        options,
+        splices,
+    )
+}
+
+/// Calculate the [`LocalChange`]s for the values that may have changed from the root value `old`,
+/// as found by [`TryToAutomerge::try_to_automerge_changed`](crate::TryToAutomerge::try_to_automerge_changed).
+///
+/// Only the changed values are compared, so this takes time proportional to their size rather
+/// than that of `old`. Gives `None` if a changed value isn't inside a map or list of `old`, such
+/// as a field that was filled in with a default when reading, in which case the whole value
+/// needs comparing instead.
+pub fn diff_changed(
+    changed: &ChangedValues,
+    old: &Value,
+    options: DiffOptions,
+    splices: &TextSplices,
+) -> Result<Option<Vec<LocalChange>>, InvalidChangeRequest> {
+    let mut changes = Vec::new();
+    for (segments, new) in changed.iter() {
+        let old = match segments.split_last() {
+            Some((last, parent)) => match (value_at(old, parent), last) {
+                (Some(Value::Map(map)), PathSegment::Key(key))
+                | (Some(Value::Table(map)), PathSegment::Key(key)) => map.get(key.as_str()),
+                (Some(Value::List(list)), PathSegment::Index(index)) if *index < list.len() => {
+                    Some(&list[*index])
+                }
+                _ => return Ok(None),
+            },
+            None => Some(old),
+        };
+        let path = segments
+            .iter()
+            .fold(Path::root(), |path, segment| match segment {
+                PathSegment::Key(key) => path.key(key.as_str()),
+                PathSegment::Index(index) => path.index((*index).try_into().unwrap()),
+            });
+        changes.append(&mut diff_inner(
+            new,
+            old,
+            path,
+            &mut segments.to_vec(),
+            options,
+            splices,
+        )?);
+    }
+    Ok(Some(changes))
+}
+
+/// Get the value at the end of `path`.
+pub(crate) fn value_at<'a>(value: &'a Value, path: &[PathSegment]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(value, |value, segment| match (value, segment) {
+            (Value::Map(map), PathSegment::Key(key))
+            | (Value::Table(map), PathSegment::Key(key)) => map.get(key.as_str()),
+            (Value::List(list), PathSegment::Index(index)) => list.get(*index),
+            _ => None,
+        })
+}
+
+/// Replace the values in the root value `value` with those that changed, where
+/// [`diff_changed`] found them all to be inside a map or list of it.
+pub(crate) fn apply_changed(value: &mut Value, changed: ChangedValues) {
+    for (path, new) in changed {
+        let (last, parent) = match path.split_last() {
+            Some(split) => split,
+            None => {
+                if let Some(new) = new {
+                    *value = new;
+                }
+                continue;
+            }
+        };
+        let parent = parent
+            .iter()
+            .try_fold(&mut *value, |value, segment| match (value, segment) {
+                (Value::Map(map), PathSegment::Key(key))
+                | (Value::Table(map), PathSegment::Key(key)) => map.get_mut(key.as_str()),
+                (Value::List(list), PathSegment::Index(index)) => list.get_mut(*index),
+                _ => None,
+            });
+        match (parent, last, new) {
+            (Some(Value::Map(map)), PathSegment::Key(key), Some(new))
+            | (Some(Value::Table(map)), PathSegment::Key(key), Some(new)) => {
+                map.insert(key.as_str().into(), new);
+            }
+            (Some(Value::Map(map)), PathSegment::Key(key), None)
+            | (Some(Value::Table(map)), PathSegment::Key(key), None) => {
+                map.remove(key.as_str());
+            }
+            (Some(Value::List(list)), PathSegment::Index(index), Some(new)) => {
+                if let Some(item) = list.get_mut(*index) {
+                    *item = new
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Diff the values at `path`, where `segments` are the same path for looking up `splices`.
+fn diff_inner(
+    new: Option<&Value>,
+    old: Option<&Value>,
+    path: Path,
+    segments: &mut Vec<PathSegment>,
+    options: DiffOptions,
+    splices: &TextSplices,
 ) -> Result<Vec<LocalChange>, InvalidChangeRequest> {
     match (new, old) {
         (None, None) => Ok(Vec::new()),
@@ -26,11 +262,17 @@ pub fn diff_with_path(
                     for (k, v) in new_map {
                         if let Some(old_v) = old_map.get(k) {
                             // changed
-                            changes.append(&mut diff_with_path(
+                            segments.push(PathSegment::Key(k.to_string()));
+                            let mut inner = diff_inner(
                                 Some(v),
                                 Some(old_v),
                                 path.clone().key(k.clone()),
-                            )?)
+                                segments,
+                                options,
+                                splices,
+                            );
+                            segments.pop();
+                            changes.append(&mut inner?)
                         } else {
                             // new
                             changes.push(LocalChange::set(path.clone().key(k.clone()), v.clone()))
@@ -49,11 +291,17 @@ pub fn diff_with_path(
                     for (k, v) in new_map {
                         if let Some(old_v) = old_map.get(k) {
                             // changed
-                            changes.append(&mut diff_with_path(
+                            segments.push(PathSegment::Key(k.to_string()));
+                            let mut inner = diff_inner(
                                 Some(v),
                                 Some(old_v),
                                 path.clone().key(k.clone()),
-                            )?)
+                                segments,
+                                options,
+                                splices,
+                            );
+                            segments.pop();
+                            changes.append(&mut inner?)
                         } else {
                             // new
                             changes.push(LocalChange::set(path.clone().key(k.clone()), v.clone()))
@@ -68,26 +316,31 @@ pub fn diff_with_path(
                     Ok(changes)
                 }
                 (Value::List(new_vec), Value::List(old_vec)) => {
+                    let (prefix, suffix) = if options.match_list_ends {
+                        common_ends(new_vec, old_vec)
+                    } else {
+                        (0, 0)
+                    };
+                    let new_middle = &new_vec[prefix..new_vec.len() - suffix];
+                    let old_middle = &old_vec[prefix..old_vec.len() - suffix];
                     let mut changes = Vec::new();
-                    // naive
-                    for (i, v) in new_vec.iter().enumerate() {
-                        if let Some(old_v) = old_vec.get(i) {
+                    // naive within the middle
+                    for (i, v) in new_middle.iter().enumerate() {
+                        let index = path.clone().index((prefix + i).try_into().unwrap());
+                        if let Some(old_v) = old_middle.get(i) {
                             // changed
-                            changes.append(&mut diff_with_path(
-                                Some(v),
-                                Some(old_v),
-                                path.clone().index(i.try_into().unwrap()),
-                            )?)
+                            segments.push(PathSegment::Index(prefix + i));
+                            let mut inner =
+                                diff_inner(Some(v), Some(old_v), index, segments, options, splices);
+                            segments.pop();
+                            changes.append(&mut inner?)
                         } else {
                             // new
-                            changes.push(LocalChange::insert(
-                                path.clone().index(i.try_into().unwrap()),
-                                v.clone(),
-                            ))
+                            changes.push(LocalChange::insert(index, v.clone()))
                         }
                     }
                     // reverse so delete from the end
-                    for i in (new_vec.len()..old_vec.len()).rev() {
+                    for i in (prefix + new_middle.len()..prefix + old_middle.len()).rev() {
                         // removed
                         changes.push(LocalChange::delete(
                             path.clone().index(i.try_into().unwrap()),
@@ -95,35 +348,12 @@ pub fn diff_with_path(
                     }
                     Ok(changes)
                 }
-                (Value::Text(new_vec), Value::Text(old_vec)) => {
-                    let mut changes = Vec::new();
-                    // naive
-                    for (i, v) in new_vec.iter().enumerate() {
-                        if let Some(old_v) = old_vec.get(i) {
-                            if v != old_v {
-                                // changed
-                                changes.push(LocalChange::set(
-                                    path.clone().index(i.try_into().unwrap()),
-                                    Value::Primitive(Primitive::Str(v.clone())),
-                                ))
-                            }
-                        } else {
-                            // new
-                            changes.push(LocalChange::insert(
-                                path.clone().index(i.try_into().unwrap()),
-                                Value::Primitive(Primitive::Str(v.clone())),
-                            ))
-                        }
+                (Value::Text(new_vec), Value::Text(old_vec)) => match splices.get(segments) {
+                    Some(splices) if replays(splices, old_vec, new_vec) => {
+                        Ok(splice_text(splices, &path))
                     }
-                    // reverse so delete from the end
-                    for i in (new_vec.len()..old_vec.len()).rev() {
-                        // removed
-                        changes.push(LocalChange::delete(
-                            path.clone().index(i.try_into().unwrap()),
-                        ))
-                    }
-                    Ok(changes)
-                }
+                    _ => Ok(diff_text(new_vec, old_vec, &path)),
+                },
                 (
                     Value::Primitive(Primitive::Str(new_string)),
                     Value::Primitive(Primitive::Str(old_string)),
@@ -264,10 +494,81 @@ pub fn diff_with_path(
     }
 }
 
+/// The lengths of the common prefix and suffix of the sequences, not overlapping.
+fn common_ends<T: PartialEq>(new: &[T], old: &[T]) -> (usize, usize) {
+    let prefix = new.iter().zip(old).take_while(|(n, o)| n == o).count();
+    let suffix = new[prefix..]
+        .iter()
+        .rev()
+        .zip(old[prefix..].iter().rev())
+        .take_while(|(n, o)| n == o)
+        .count();
+    (prefix, suffix)
+}
+
+/// Whether making the `splices` to the `old` text gives the `new` one.
+fn replays(splices: &[TextSplice], old: &[SmolStr], new: &[SmolStr]) -> bool {
+    let mut text = old.to_vec();
+    splices
+        .iter()
+        .all(|splice| splice.apply(&mut text).is_some())
+        && text == new
+}
+
+/// The changes making each of the `splices`, in order.
+fn splice_text(splices: &[TextSplice], path: &Path) -> Vec<LocalChange> {
+    let index = |i: usize| path.clone().index(i.try_into().unwrap());
+
+    let mut changes = Vec::new();
+    for splice in splices {
+        for _ in 0..splice.delete {
+            changes.push(LocalChange::delete(index(splice.index)))
+        }
+        for (i, grapheme) in splice.insert.iter().enumerate() {
+            changes.push(LocalChange::insert(
+                index(splice.index + i),
+                Value::Primitive(Primitive::Str(grapheme.clone())),
+            ))
+        }
+    }
+    changes
+}
+
+/// Splice the graphemes that changed between the common prefix and suffix of the texts.
+///
+/// A single edit, such as typing or deleting a selection, becomes exactly that splice.
+fn diff_text(new: &[SmolStr], old: &[SmolStr], path: &Path) -> Vec<LocalChange> {
+    let (prefix, suffix) = common_ends(new, old);
+    let new_middle = &new[prefix..new.len() - suffix];
+    let old_middle = &old[prefix..old.len() - suffix];
+    let index = |i: usize| path.clone().index(i.try_into().unwrap());
+
+    let mut changes = Vec::new();
+    let replaced = new_middle.len().min(old_middle.len());
+    for (i, grapheme) in new_middle[..replaced].iter().enumerate() {
+        changes.push(LocalChange::set(
+            index(prefix + i),
+            Value::Primitive(Primitive::Str(grapheme.clone())),
+        ))
+    }
+    for (i, grapheme) in new_middle[replaced..].iter().enumerate() {
+        changes.push(LocalChange::insert(
+            index(prefix + replaced + i),
+            Value::Primitive(Primitive::Str(grapheme.clone())),
+        ))
+    }
+    // reverse so delete from the end
+    for i in (prefix + replaced..prefix + old_middle.len()).rev() {
+        changes.push(LocalChange::delete(index(i)))
+    }
+    changes
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
+    use automergeable_traits::{ApplyPatch, Text};
     use insta::assert_debug_snapshot;
 
     use super::*;
@@ -432,6 +733,43 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn diff_vecs_matching_ends() {
+        let old_vec: Vec<Value> = vec![
+            Primitive::Str("b".into()).into(),
+            Primitive::Str("c".into()).into(),
+        ];
+        let mut new_vec = old_vec.clone();
+        new_vec.insert(0, Primitive::Str("a".into()).into());
+        let options = DiffOptions {
+            match_list_ends: true,
+        };
+
+        let changes = diff_with_options(
+            Some(&Value::List(new_vec.clone())),
+            Some(&Value::List(old_vec.clone())),
+            Path::root(),
+            options,
+        )
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![LocalChange::insert(
+                Path::root().index(0),
+                Primitive::Str("a".into()).into()
+            )]
+        );
+
+        let changes = diff_with_options(
+            Some(&Value::List(old_vec)),
+            Some(&Value::List(new_vec)),
+            Path::root(),
+            options,
+        )
+        .unwrap();
+        assert_eq!(changes, vec![LocalChange::delete(Path::root().index(0))]);
+    }
+
     #[test]
     fn diff_text() {
         let mut old_text = Vec::new();
@@ -512,6 +850,78 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn diff_text_splices_middle() {
+        let old = Value::Text(vec!["a".into(), "b".into(), "c".into()]);
+        let new = Value::Text(vec!["a".into(), "x".into(), "y".into(), "c".into()]);
+        let changes = diff_values(&new, &old).unwrap();
+        // "b" is replaced and "y" inserted after it, leaving "a" and "c" alone
+        assert_eq!(
+            changes,
+            vec![
+                LocalChange::set(
+                    Path::root().index(1),
+                    Value::Primitive(Primitive::Str("x".into()))
+                ),
+                LocalChange::insert(
+                    Path::root().index(2),
+                    Value::Primitive(Primitive::Str("y".into()))
+                ),
+            ]
+        );
+
+        let changes = diff_values(&old, &new).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                LocalChange::set(
+                    Path::root().index(1),
+                    Value::Primitive(Primitive::Str("b".into()))
+                ),
+                LocalChange::delete(Path::root().index(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_text_with_splices() {
+        let old = Value::Text("hello".chars().map(|c| c.to_string().into()).collect());
+        let new = Value::Text("helllo".chars().map(|c| c.to_string().into()).collect());
+        let mut root = HashMap::new();
+        root.insert("text".into(), old);
+        let old = Value::Map(root.clone());
+        root.insert("text".into(), new);
+        let new = Value::Map(root);
+
+        // typing an "l" after the "e" rather than the one found by comparing
+        let mut text = Text::from("hello");
+        text.splice(2, 0, "l");
+        let mut splices = TextSplices::default();
+        text.take_splices(&mut vec![PathSegment::Key("text".to_owned())], &mut splices);
+        let changes = diff_with_splices(&new, &old, DiffOptions::default(), &splices).unwrap();
+        assert_eq!(
+            changes,
+            vec![LocalChange::insert(
+                Path::root().key("text").index(2),
+                Value::Primitive(Primitive::Str("l".into()))
+            )]
+        );
+
+        // splices that don't make the new text are ignored
+        let changes = diff_with_splices(&old, &old, DiffOptions::default(), &splices).unwrap();
+        assert!(changes.is_empty());
+        let changes =
+            diff_with_options(Some(&new), Some(&old), Path::root(), DiffOptions::default())
+                .unwrap();
+        assert_eq!(
+            changes,
+            vec![LocalChange::insert(
+                Path::root().key("text").index(4),
+                Value::Primitive(Primitive::Str("l".into()))
+            )]
+        );
+    }
+
     #[test]
     fn new_and_empty() {
         let old = Value::Primitive(Primitive::Null);
@@ -567,4 +977,57 @@ mod tests {
         )
         "###);
     }
+
+    #[test]
+    fn diff_changed_values() {
+        let mut hm = HashMap::new();
+        hm.insert("a".into(), Value::Primitive(Primitive::Uint(1)));
+        hm.insert("b".into(), Value::Primitive(Primitive::Uint(2)));
+        let mut old = Value::Map(hm);
+
+        let mut changed = ChangedValues::default();
+        changed.push(
+            &[PathSegment::Key("a".to_owned())],
+            Some(Value::Primitive(Primitive::Uint(3))),
+        );
+        changed.push(&[PathSegment::Key("b".to_owned())], None);
+        let changes = diff_changed(
+            &changed,
+            &old,
+            DiffOptions::default(),
+            &TextSplices::default(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                LocalChange::set(Path::root().key("a"), Value::Primitive(Primitive::Uint(3))),
+                LocalChange::delete(Path::root().key("b")),
+            ]
+        );
+
+        apply_changed(&mut old, changed);
+        let mut hm = HashMap::new();
+        hm.insert("a".into(), Value::Primitive(Primitive::Uint(3)));
+        assert_eq!(old, Value::Map(hm));
+
+        // a value inside a map that isn't there yet can't be diffed on its own
+        let mut changed = ChangedValues::default();
+        changed.push(
+            &[
+                PathSegment::Key("c".to_owned()),
+                PathSegment::Key("d".to_owned()),
+            ],
+            Some(Value::Primitive(Primitive::Uint(4))),
+        );
+        assert!(diff_changed(
+            &changed,
+            &old,
+            DiffOptions::default(),
+            &TextSplices::default()
+        )
+        .unwrap()
+        .is_none());
+    }
 }
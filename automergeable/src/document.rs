@@ -1,14 +1,19 @@
-use std::{error::Error, fmt::Debug};
+use std::{collections::HashMap, convert::TryInto, error::Error, fmt::Debug, rc::Rc, sync::Arc};
 
-use automerge::{Path, Value};
+use automerge::{InvalidChangeRequest, LocalChange, Path, Primitive, Value};
 use automerge_frontend::MutableDocument;
-use automerge_protocol::Patch;
+use automerge_protocol::{Diff, Patch};
 
-use crate::Automergeable;
+use crate::{ApplyPatch, ChangedValues, DiffOptions, DiffReport, RootAutomergeable, TextSplices};
 
 /// An error type for change operations on documents.
+///
+/// `E` is the error from the change closure and `FE` the error from the document's frontend.
 #[derive(Debug, thiserror::Error)]
-pub enum DocumentChangeError<E: Error = std::convert::Infallible> {
+pub enum DocumentChangeError<
+    E: Error = std::convert::Infallible,
+    FE: Error = automerge_frontend::InvalidPatch,
+> {
     /// An invalid change request was created.
     ///
     /// Automerge imposes some limits on what can be changed and how. See the
@@ -18,18 +23,117 @@ pub enum DocumentChangeError<E: Error = std::convert::Infallible> {
     /// A failure to convert the value in automerge to a typed value.
     #[error(transparent)]
     FromError(#[from] crate::FromAutomergeError),
+    /// The typed value couldn't be represented in automerge.
+    #[error(transparent)]
+    ToError(#[from] crate::ToAutomergeError),
     /// A custom error from the users closure.
     #[error(
         "change error:
         {0}"
     )]
     ChangeError(E),
+    /// The document's in-memory backend failed to apply the change.
+    ///
+    /// The document is left as it was before the change.
+    #[error("backend error: {0}")]
+    BackendError(#[source] automerge_backend::AutomergeError),
+    /// The frontend failed to apply the patch from the document's in-memory backend.
+    #[error("frontend error: {0}")]
+    FrontendError(FE),
+    /// The document was [bootstrapped](Document::bootstrap) but the patch for its genesis change
+    /// hasn't been applied yet, so changing it would create conflicting objects.
+    #[error("the genesis change hasn't been applied to the document yet")]
+    GenesisNotApplied,
+    /// The change was larger than the document's [`ChangeBudget`] allows.
+    #[error("change exceeded budget: {actual} {limit} but at most {allowed} are allowed")]
+    BudgetExceeded {
+        /// The limit that was exceeded.
+        limit: BudgetLimit,
+        /// The size of the rejected change.
+        actual: usize,
+        /// The size allowed by the budget.
+        allowed: usize,
+    },
+}
+
+/// Limits on the size of a single change made through a [`Document`].
+///
+/// Changes that go over the budget are rejected before reaching the frontend so they never need
+/// to be synced to other peers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeBudget {
+    /// The maximum number of operations in a change.
+    pub max_operations: Option<usize>,
+    /// The maximum estimated number of bytes of values in a change.
+    pub max_bytes: Option<usize>,
+}
+
+impl ChangeBudget {
+    /// Check whether the report fits in this budget.
+    pub fn allows(&self, report: &DiffReport) -> bool {
+        self.exceeded(report).is_none()
+    }
+
+    /// Find the first limit that the report goes over, along with the actual and allowed sizes.
+    pub fn exceeded(&self, report: &DiffReport) -> Option<(BudgetLimit, usize, usize)> {
+        match (self.max_operations, self.max_bytes) {
+            (Some(max), _) if report.operations > max => {
+                Some((BudgetLimit::Operations, report.operations, max))
+            }
+            (_, Some(max)) if report.bytes > max => Some((BudgetLimit::Bytes, report.bytes, max)),
+            _ => None,
+        }
+    }
 }
 
+/// A limit in a [`ChangeBudget`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// The number of operations in a change.
+    Operations,
+    /// The estimated number of bytes of values in a change.
+    Bytes,
+}
+
+impl std::fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Operations => write!(f, "operations"),
+            Self::Bytes => write!(f, "bytes"),
+        }
+    }
+}
+
+/// An error type for forking and merging documents.
+#[derive(Debug, thiserror::Error)]
+pub enum MergeError {
+    /// The document doesn't have an in-memory backend to take the history from.
+    #[error("document has no backend")]
+    NoBackend,
+    /// The backend failed to apply the changes.
+    #[error("backend error: {0}")]
+    BackendError(#[from] automerge_backend::AutomergeError),
+    /// The merged changes couldn't be applied to the document.
+    #[error(transparent)]
+    ApplyPatch(#[from] ApplyPatchError<automerge_frontend::InvalidPatch>),
+}
+
+/// A summary of a merge between documents.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MergeReport {
+    /// The number of changes brought in from the other document.
+    pub changes: usize,
+    /// The paths that have conflicting values after the merge.
+    pub conflicts: Vec<Path>,
+}
+
+/// An error type for applying patches to documents.
 #[derive(Debug, thiserror::Error)]
 pub enum ApplyPatchError<E: Error> {
+    /// The frontend failed to apply the patch.
     #[error("frontend error: {0}")]
     FrontendError(E),
+    /// A failure to convert the patched value in automerge to a typed value.
     #[error(transparent)]
     FromError(#[from] crate::FromAutomergeError),
 }
@@ -49,6 +153,17 @@ pub trait Frontend {
         E: Error;
 
     fn apply_patch(&mut self, patch: Patch) -> Result<(), Self::Error>;
+
+    /// Discard any local changes that haven't been applied by a backend, resetting the frontend
+    /// to the state in `patch`.
+    ///
+    /// The `timestamper` is the one the document was built with, if any, for frontends that are
+    /// recreated to reset.
+    fn reset(
+        &mut self,
+        patch: Patch,
+        timestamper: Option<Box<dyn Fn() -> Option<i64>>>,
+    ) -> Result<(), Self::Error>;
 }
 
 impl Frontend for automerge::Frontend {
@@ -74,8 +189,49 @@ impl Frontend for automerge::Frontend {
     fn apply_patch(&mut self, patch: Patch) -> Result<(), Self::Error> {
         self.apply_patch(patch)
     }
+
+    /// The new frontend keeps the actor id and uses the given timestamper.
+    fn reset(
+        &mut self,
+        patch: Patch,
+        timestamper: Option<Box<dyn Fn() -> Option<i64>>>,
+    ) -> Result<(), Self::Error> {
+        let mut frontend = match timestamper {
+            Some(timestamper) => Self::new_with_timestamper(timestamper),
+            None => Self::new(),
+        };
+        frontend.actor_id = self.actor_id.clone();
+        frontend.apply_patch(patch)?;
+        *self = frontend;
+        Ok(())
+    }
 }
 
+/// A timestamper shared between a document and the frontends it creates.
+#[derive(Clone)]
+pub(crate) struct Timestamper(Rc<dyn Fn() -> Option<i64>>);
+
+impl Timestamper {
+    pub(crate) fn new(timestamper: Box<dyn Fn() -> Option<i64>>) -> Self {
+        Self(Rc::from(timestamper))
+    }
+
+    /// Box a handle to the timestamper to give to a frontend.
+    pub(crate) fn boxed(&self) -> Box<dyn Fn() -> Option<i64>> {
+        let timestamper = Rc::clone(&self.0);
+        Box::new(move || timestamper())
+    }
+}
+
+impl Debug for Timestamper {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Timestamper")
+    }
+}
+
+/// The actor that creates the genesis change in [`Document::bootstrap`].
+pub const GENESIS_ACTOR: uuid::Uuid = uuid::Uuid::nil();
+
 /// A typed automerge document, wrapping a typical frontend.
 ///
 /// This provides similar functionality to an automerge frontend (including [`Deref`] to one) but with
@@ -83,33 +239,255 @@ impl Frontend for automerge::Frontend {
 ///
 /// For instance from a document we can get the value as a typical Rust struct and perform
 /// automerge change operations on it with automatic diffing behind the scenes.
+///
+/// The root of an automerge document is always a map so `T` must be [`RootAutomergeable`], other
+/// types can be stored by wrapping them in [`Rooted`](crate::Rooted).
 #[derive(Debug)]
 pub struct Document<T, F>
 where
-    T: Automergeable,
+    T: RootAutomergeable,
     F: Frontend,
 {
     frontend: F,
     value: T,
-    original: Value,
+    /// The automerge value that `value` was last synchronised with.
+    ///
+    /// This is shared so that snapshots are cheap to take. Changes update it in place, but copy it
+    /// in full first while a snapshot or pending change holds it.
+    original: Arc<Value>,
+    budget: ChangeBudget,
+    diff_options: DiffOptions,
+    /// Whether local changes are tracked until a patch from the backend acknowledges them.
+    track_pending: bool,
+    /// Local changes that haven't been acknowledged by a patch from the backend yet.
+    pending: Vec<PendingChange>,
+    /// The value as last confirmed by the backend, without any pending changes.
+    ///
+    /// This is only tracked while there are pending changes.
+    confirmed: Option<Arc<Value>>,
+    /// Whether a remote patch has arrived since the pending changes were made, so they have been
+    /// rebased.
+    rebased: bool,
+    /// An in-memory backend that changes are applied to immediately, if the document owns one.
+    backend: Option<automerge::Backend>,
+    /// The actor of the genesis change from [`Document::bootstrap`], until a patch including it
+    /// is applied.
+    genesis: Option<automerge_protocol::ActorId>,
+    /// The timestamper the frontend was built with, to give to it again when it is reset.
+    timestamper: Option<Timestamper>,
+}
+
+/// A local change waiting for the backend, along with the values either side of it so that it
+/// can be rebased onto remote patches.
+///
+/// These can be persisted, with serde when the `serde` feature is enabled, and given back to
+/// [`Document::restore_pending`] after a restart so that offline edits aren't lost. The values
+/// are serialized in the [tagged JSON encoding](crate::json) so they come back exactly.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PendingChange {
+    change: automerge_protocol::Change,
+    #[cfg_attr(feature = "serde", serde(with = "tagged_value"))]
+    before: Arc<Value>,
+    #[cfg_attr(feature = "serde", serde(with = "tagged_value"))]
+    after: Arc<Value>,
+}
+
+/// Serialize values in the tagged JSON encoding, as automerge values have no serde encoding that
+/// keeps their types.
+#[cfg(feature = "serde")]
+mod tagged_value {
+    use std::sync::Arc;
+
+    use ::serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+    use automerge::Value;
+
+    use crate::json::{from_json, to_json, TaggedJson};
+
+    pub fn serialize<S>(value: &Arc<Value>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        to_json(value)
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Arc<Value>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let json = TaggedJson(Deserialize::deserialize(deserializer)?);
+        from_json(&json.0).map(Arc::new).map_err(de::Error::custom)
+    }
+}
+
+impl PendingChange {
+    /// Create a pending change from the change and the values of the document before and after
+    /// it.
+    pub fn new(change: automerge_protocol::Change, before: Value, after: Value) -> Self {
+        Self {
+            change,
+            before: Arc::new(before),
+            after: Arc::new(after),
+        }
+    }
+
+    /// The change to send to the backend.
+    pub const fn change(&self) -> &automerge_protocol::Change {
+        &self.change
+    }
+
+    /// The value of the document before the change.
+    pub fn before(&self) -> &Value {
+        &self.before
+    }
+
+    /// The value of the document after the change.
+    pub fn after(&self) -> &Value {
+        &self.after
+    }
+}
+
+/// How to bring a document's original value up to date with a change.
+enum OriginalUpdate {
+    /// Replace the values that changed.
+    Changed(ChangedValues),
+    /// Replace the whole value.
+    Replace(Value),
 }
 
 impl<T, F> Document<T, F>
 where
-    T: Automergeable + Clone,
+    T: RootAutomergeable,
     F: Frontend,
 {
     /// Construct a new document.
     pub fn new(frontend: F) -> Self {
+        Self::load(frontend, None, Err).expect("Failed to load value")
+    }
+
+    /// Construct a new document that keeps its own in-memory backend.
+    ///
+    /// Changes are applied to the backend as they are made so there is no need to apply patches
+    /// manually, and the document can be [forked](Document::fork) and
+    /// [merged](Document::merge).
+    pub fn with_backend(frontend: F, backend: automerge::Backend) -> Self {
+        Self::load(frontend, Some(backend), Err).expect("Failed to load value")
+    }
+
+    /// Load the value from the frontend, bringing it up to date with the backend first if there
+    /// is one.
+    ///
+    /// `recover` is called with the error if the value can't be decoded, to provide a value to
+    /// use instead.
+    pub(crate) fn load<R>(
+        mut frontend: F,
+        backend: Option<automerge::Backend>,
+        recover: R,
+    ) -> Result<Self, crate::FromAutomergeError>
+    where
+        R: FnOnce(crate::FromAutomergeError) -> Result<T, crate::FromAutomergeError>,
+    {
+        if let Some(backend) = &backend {
+            let patch = backend
+                .get_patch()
+                .expect("Failed to get patch from backend");
+            frontend
+                .apply_patch(patch)
+                .expect("Failed to apply backend patch");
+        }
         let original = frontend
             .get_value(&Path::root())
             .expect("Failed to get root value")
             .expect("No root value");
-        let value = T::from_automerge(&original).expect("Failed to load value");
-        Self {
+        let value = T::from_automerge(&original).or_else(recover)?;
+        Ok(Self {
             frontend,
             value,
-            original,
+            original: Arc::new(original),
+            budget: ChangeBudget::default(),
+            diff_options: DiffOptions::default(),
+            track_pending: false,
+            pending: Vec::new(),
+            confirmed: None,
+            rebased: false,
+            backend,
+            genesis: None,
+            timestamper: None,
+        })
+    }
+
+    /// Get the in-memory backend of this document, if it has one.
+    pub const fn backend(&self) -> Option<&automerge::Backend> {
+        self.backend.as_ref()
+    }
+
+    /// Construct a new document along with a genesis change creating the default structure of
+    /// `T`.
+    ///
+    /// The genesis change is made by [`GENESIS_ACTOR`] with a fixed sequence number and no
+    /// timestamp, so every peer bootstrapping the same `T` produces the same change. Once applied
+    /// to the backend, the nested maps, lists and text of `T` are then the same objects on every
+    /// replica, rather than conflicting ones that lose each other's edits when merged.
+    ///
+    /// There is no genesis change if the default value of `T` is an empty map. Otherwise it has to
+    /// be applied to the backend, and the patch from that [applied](Self::apply_patch) to this
+    /// document, before making any changes. Until then changes fail with
+    /// [`DocumentChangeError::GenesisNotApplied`], as they would create their own objects that
+    /// conflict with those of the genesis change.
+    pub fn bootstrap(
+        frontend: F,
+    ) -> Result<
+        (Self, Option<automerge_protocol::Change>),
+        DocumentChangeError<std::convert::Infallible, F::Error>,
+    >
+    where
+        T: Default,
+    {
+        let mut changes = Vec::new();
+        structure_changes(
+            &T::default().try_to_automerge()?,
+            Path::root(),
+            &mut changes,
+        );
+        let mut genesis = automerge::Frontend::new_with_timestamper_and_actor_id(
+            Box::new(|| None),
+            GENESIS_ACTOR,
+        );
+        let ((), change) = genesis.change::<_, _, InvalidChangeRequest>(None, |doc| {
+            for change in changes {
+                doc.add_change(change)?
+            }
+            Ok(())
+        })?;
+        let mut document = Self::new(frontend);
+        document.genesis = change.as_ref().map(|change| change.actor_id.clone());
+        Ok((document, change))
+    }
+
+    /// Set the limits for each change made to this document.
+    pub fn set_change_budget(&mut self, budget: ChangeBudget) {
+        self.budget = budget
+    }
+
+    /// Set the options for diffing the typed value to find the changes it makes.
+    pub fn set_diff_options(&mut self, options: DiffOptions) {
+        self.diff_options = options
+    }
+
+    /// Set whether local changes are tracked until the backend acknowledges them, for when the
+    /// backend applies them asynchronously.
+    ///
+    /// While there are [pending changes](Self::pending_changes) the typed value stays optimistic,
+    /// with remote patches applied under the pending changes. Rebasing them decodes the whole
+    /// value so this is off by default. Turning it off forgets any pending changes.
+    pub fn set_track_pending(&mut self, track: bool) {
+        self.track_pending = track;
+        if !track {
+            self.pending.clear();
+            self.confirmed = None;
+            self.rebased = false;
         }
     }
 
@@ -118,6 +496,14 @@ where
         &self.value
     }
 
+    /// Get a snapshot of the automerge value backing the typed value.
+    ///
+    /// This is cheap to take and doesn't copy the document, but the document copies its value the
+    /// next time it is updated while the snapshot is held.
+    pub fn snapshot(&self) -> Arc<Value> {
+        Arc::clone(&self.original)
+    }
+
     fn get_root(&self) -> Result<Value, F::Error> {
         Ok(self
             .frontend
@@ -129,15 +515,26 @@ where
         &mut self,
         message: Option<String>,
         change: C,
-    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E>>
+    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E, F::Error>>
     where
+        T: Clone,
         E: Error,
         C: FnOnce(&mut T) -> Result<O, E>,
     {
+        if self.genesis.is_some() {
+            return Err(DocumentChangeError::GenesisNotApplied);
+        }
+        // the change is made to a copy so a failing or panicking closure leaves the value as it was
         let mut new_t = self.value.clone();
         let res = change(&mut new_t).map_err(DocumentChangeError::ChangeError)?;
-        let new_original = new_t.to_automerge();
-        let changes = crate::diff_values(&new_original, &self.original)?;
+        let (changes, update) = self.diff_value(&mut new_t)?;
+        if let Some((limit, actual, allowed)) = self.budget.exceeded(&DiffReport::new(&changes)) {
+            return Err(DocumentChangeError::BudgetExceeded {
+                limit,
+                actual,
+                allowed,
+            });
+        }
         let change =
             self.frontend
                 .change::<_, automerge::InvalidChangeRequest>(message, |doc| {
@@ -146,17 +543,127 @@ where
                     }
                     Ok(())
                 })?;
+        if let (Some(change), Some(backend)) = (&change, &mut self.backend) {
+            let patch = match backend.apply_local_change(change.clone()) {
+                Ok((patch, _)) => patch,
+                Err(e) => {
+                    // the frontend would wait forever for the rejected change, so go back to what
+                    // the backend has
+                    let patch = backend
+                        .get_patch()
+                        .map_err(DocumentChangeError::BackendError)?;
+                    self.frontend
+                        .reset(patch, self.frontend_timestamper())
+                        .map_err(DocumentChangeError::FrontendError)?;
+                    return Err(DocumentChangeError::BackendError(e));
+                }
+            };
+            self.value = new_t;
+            self.update_original(update);
+            self.apply_patch(patch).map_err(|e| match e {
+                ApplyPatchError::FromError(e) => DocumentChangeError::FromError(e),
+                ApplyPatchError::FrontendError(e) => DocumentChangeError::FrontendError(e),
+            })?;
+            return Ok((res, change));
+        }
+        match (&change, self.track_pending) {
+            (Some(change), true) => {
+                let before = Arc::clone(&self.original);
+                if self.pending.is_empty() {
+                    self.confirmed = Some(Arc::clone(&before));
+                }
+                self.update_original(update);
+                self.pending.push(PendingChange {
+                    change: change.clone(),
+                    before,
+                    after: Arc::clone(&self.original),
+                });
+            }
+            _ => self.update_original(update),
+        }
         self.value = new_t;
-        self.original = new_original;
         Ok((res, change))
     }
 
+    /// Find the changes from the value to `new`, along with how to update the original value to
+    /// match.
+    ///
+    /// Only the parts of `new` that may differ from the value are converted and compared, see
+    /// [`TryToAutomerge::try_to_automerge_changed`](crate::TryToAutomerge::try_to_automerge_changed).
+    fn diff_value<E>(
+        &self,
+        new: &mut T,
+    ) -> Result<(Vec<LocalChange>, OriginalUpdate), DocumentChangeError<E, F::Error>>
+    where
+        E: Error,
+    {
+        let mut splices = TextSplices::default();
+        new.take_splices(&mut Vec::new(), &mut splices);
+        let mut changed = ChangedValues::default();
+        new.try_to_automerge_changed(&self.value, &mut Vec::new(), &mut changed)?;
+        if let Some(changes) =
+            crate::diff::diff_changed(&changed, &self.original, self.diff_options, &splices)?
+        {
+            return Ok((changes, OriginalUpdate::Changed(changed)));
+        }
+        // part of the value isn't in the original yet, so compare it all
+        let new_original = new.try_to_automerge()?;
+        let changes = crate::diff::diff_with_splices(
+            &new_original,
+            &self.original,
+            self.diff_options,
+            &splices,
+        )?;
+        Ok((changes, OriginalUpdate::Replace(new_original)))
+    }
+
+    /// Bring the original value up to date after a change.
+    ///
+    /// This is done in place, unless a snapshot or pending change still holds the original.
+    fn update_original(&mut self, update: OriginalUpdate) {
+        match update {
+            OriginalUpdate::Changed(changed) => {
+                crate::diff::apply_changed(Arc::make_mut(&mut self.original), changed)
+            }
+            OriginalUpdate::Replace(value) => self.original = Arc::new(value),
+        }
+    }
+
+    /// Calculate the changes that the closure would make, without applying them.
+    ///
+    /// This operates on a copy of the value so the document is left untouched.
+    pub fn preview_change<C, O, E>(
+        &self,
+        change: C,
+    ) -> Result<(O, Vec<LocalChange>, DiffReport), DocumentChangeError<E, F::Error>>
+    where
+        T: Clone,
+        E: Error,
+        C: FnOnce(&mut T) -> Result<O, E>,
+    {
+        let mut new_t = self.value.clone();
+        let res = change(&mut new_t).map_err(DocumentChangeError::ChangeError)?;
+        let (changes, _) = self.diff_value(&mut new_t)?;
+        let report = DiffReport::new(&changes);
+        Ok((res, changes, report))
+    }
+
     /// Perform a change on the frontend.
+    ///
+    /// The closure edits a copy of the value, then the parts of it that may have changed are
+    /// converted and compared with the document to find the changes. Structs, maps and lists are
+    /// compared field by field, and values behind an [`Arc`] or [`Rc`](std::rc::Rc) that is still
+    /// shared with the old value are skipped. Keeping large parts of the value behind an `Arc`,
+    /// and editing them with [`Arc::make_mut`], makes changes take time proportional to what they
+    /// touch rather than the size of the whole document.
+    ///
+    /// Copying the value for the closure is also cheap for the parts behind an `Arc`.
     pub fn change<C, O, E>(
         &mut self,
         change: C,
-    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E>>
+    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E, F::Error>>
     where
+        T: Clone,
         E: Error,
         C: FnOnce(&mut T) -> Result<O, E>,
     {
@@ -168,32 +675,286 @@ where
         &mut self,
         message: String,
         change: C,
-    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E>>
+    ) -> Result<(O, Option<automerge_protocol::Change>), DocumentChangeError<E, F::Error>>
     where
+        T: Clone,
         E: Error,
         C: FnOnce(&mut T) -> Result<O, E>,
     {
         self.change_inner(Some(message), change)
     }
 
+    /// Get the local changes that haven't been acknowledged by the backend yet, oldest first.
+    ///
+    /// This is always empty unless [tracking](Self::set_track_pending) is turned on.
+    pub fn pending_changes(&self) -> &[PendingChange] {
+        &self.pending
+    }
+
+    /// Restore pending changes that were persisted before a restart, turning on
+    /// [tracking](Self::set_track_pending).
+    ///
+    /// The changes are rebased onto the current value and presented optimistically until the
+    /// backend acknowledges them, so they still need to be sent to the backend again.
+    pub fn restore_pending(
+        &mut self,
+        pending: Vec<PendingChange>,
+    ) -> Result<(), crate::FromAutomergeError> {
+        self.track_pending = true;
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if self.pending.is_empty() {
+            self.confirmed = Some(Arc::clone(&self.original));
+        }
+        self.pending.extend(pending);
+        self.rebased = true;
+        self.rebase()
+    }
+
     /// Apply a patch to the frontend, updating the stored value in the process.
+    ///
+    /// Only the parts of the value that the patch refers to are fetched from the frontend and
+    /// decoded.
+    ///
+    /// While there are pending local changes the typed value stays optimistic: remote patches are
+    /// applied to the confirmed value and the pending changes rebased on top of it. Patches that
+    /// acknowledge pending changes don't need a rebase.
     pub fn apply_patch(&mut self, patch: Patch) -> Result<(), ApplyPatchError<F::Error>> {
+        self.check_genesis(&patch);
+        let diff = patch.diffs.clone();
+        // the backend may acknowledge changes out of order, such as when they are sent again
+        let acknowledged = self.pending.iter().position(|p| {
+            patch.actor.as_ref() == Some(&p.change.actor_id) && patch.seq == Some(p.change.seq)
+        });
         self.frontend
             .apply_patch(patch)
             .map_err(ApplyPatchError::FrontendError)?;
-        self.refresh_value()?;
+
+        if let Some(confirmed) = &mut self.confirmed {
+            if let Some(diff) = &diff {
+                crate::rebase::apply_diff(Arc::make_mut(confirmed), diff);
+            }
+            match acknowledged {
+                Some(index) => {
+                    self.pending.remove(index);
+                }
+                None => self.rebased = true,
+            }
+            if self.pending.is_empty() {
+                // everything has been confirmed so the confirmed value is the source of truth
+                // again, the optimistic value only differs from it if remote patches came in
+                self.original = self.confirmed.take().expect("confirmed value with pending");
+                if std::mem::take(&mut self.rebased) {
+                    self.value = T::from_automerge(&self.original)?;
+                }
+            } else if acknowledged.is_none() {
+                self.rebase()?;
+            }
+        } else if let Some(diff) = diff {
+            self.refresh_original(&diff)
+                .map_err(ApplyPatchError::FrontendError)?;
+            self.value.apply_patch(&diff, &self.original)?;
+        }
         Ok(())
     }
 
-    /// Set the internal typed value to that obtained from the frontend.
-    ///
-    /// This is intended to be used in case of interacting with the frontend directly.
-    fn refresh_value(&mut self) -> Result<(), ApplyPatchError<F::Error>> {
-        // TODO: change this to a new error type
-        self.original = self.get_root().map_err(ApplyPatchError::FrontendError)?;
+    /// Reset the frontend to the state in the patch from the backend, dropping any pending
+    /// changes, such as after the backend rejects a local change.
+    pub(crate) fn reset(&mut self, patch: Patch) -> Result<(), ApplyPatchError<F::Error>> {
+        self.check_genesis(&patch);
+        self.frontend
+            .reset(patch, self.frontend_timestamper())
+            .map_err(ApplyPatchError::FrontendError)?;
+        self.pending.clear();
+        self.confirmed = None;
+        self.rebased = false;
+        self.original = Arc::new(self.get_root().map_err(ApplyPatchError::FrontendError)?);
         self.value = T::from_automerge(&self.original)?;
         Ok(())
     }
+
+    /// Keep the timestamper the frontend was built with, for when it is reset.
+    pub(crate) fn set_timestamper(&mut self, timestamper: Timestamper) {
+        self.timestamper = Some(timestamper);
+    }
+
+    /// A timestamper for a new frontend, sharing the one the document was built with.
+    fn frontend_timestamper(&self) -> Option<Box<dyn Fn() -> Option<i64>>> {
+        self.timestamper.as_ref().map(Timestamper::boxed)
+    }
+
+    /// Stop waiting for the genesis change once a patch includes it.
+    fn check_genesis(&mut self, patch: &Patch) {
+        if let Some(genesis) = &self.genesis {
+            if patch.clock.contains_key(genesis) {
+                self.genesis = None;
+            }
+        }
+    }
+
+    /// Rebuild the optimistic value by rebasing the pending changes onto the confirmed value.
+    fn rebase(&mut self) -> Result<(), crate::FromAutomergeError> {
+        if let Some(confirmed) = &self.confirmed {
+            let mut current = Value::clone(confirmed);
+            for pending in &self.pending {
+                current = crate::rebase::merge(&pending.before, &pending.after, &current);
+            }
+            self.original = Arc::new(current);
+            self.value = T::from_automerge(&self.original)?;
+        }
+        Ok(())
+    }
+
+    /// Update the original value with the root keys that the diff touches.
+    fn refresh_original(&mut self, diff: &Diff) -> Result<(), F::Error> {
+        if let (Diff::Map(map_diff), Value::Map(original)) =
+            (diff, Arc::make_mut(&mut self.original))
+        {
+            for key in map_diff.props.keys() {
+                match self.frontend.get_value(&Path::root().key(key.clone()))? {
+                    Some(value) => {
+                        original.insert(key.as_str().into(), value);
+                    }
+                    None => {
+                        original.remove(key.as_str());
+                    }
+                }
+            }
+            return Ok(());
+        }
+        self.original = Arc::new(self.get_root()?);
+        Ok(())
+    }
+}
+
+impl<T> Document<T, automerge::Frontend>
+where
+    T: RootAutomergeable,
+{
+    /// Create an independent copy of this document with a new actor, sharing its history.
+    ///
+    /// Changes made to the fork can be brought back with [`merge`](Document::merge).
+    pub fn fork(&self) -> Result<Self, MergeError> {
+        let backend = self.backend.as_ref().ok_or(MergeError::NoBackend)?;
+        let changes = backend
+            .get_changes(&[])
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut fork = Self::with_backend(automerge::Frontend::new(), automerge::Backend::new());
+        fork.apply_backend_changes(changes)?;
+        Ok(fork)
+    }
+
+    /// Merge the changes from another document into this one, refreshing the typed value.
+    ///
+    /// Both documents need an in-memory backend.
+    pub fn merge(&mut self, other: &Self) -> Result<MergeReport, MergeError> {
+        let ours = self.backend.as_ref().ok_or(MergeError::NoBackend)?;
+        let theirs = other.backend.as_ref().ok_or(MergeError::NoBackend)?;
+        let changes = theirs
+            .get_changes(&[])
+            .into_iter()
+            .filter(|c| ours.get_change_by_hash(&c.hash).is_none())
+            .cloned()
+            .collect::<Vec<_>>();
+        self.apply_backend_changes(changes)
+    }
+
+    fn apply_backend_changes(
+        &mut self,
+        changes: Vec<automerge::Change>,
+    ) -> Result<MergeReport, MergeError> {
+        let mut report = MergeReport {
+            changes: changes.len(),
+            conflicts: Vec::new(),
+        };
+        if changes.is_empty() {
+            return Ok(report);
+        }
+        let backend = self.backend.as_mut().ok_or(MergeError::NoBackend)?;
+        let patch = backend.apply_changes(changes)?;
+        if let Some(diff) = &patch.diffs {
+            find_conflicts(diff, Path::root(), &mut report.conflicts);
+        }
+        self.apply_patch(patch)?;
+        Ok(report)
+    }
+}
+
+/// Collect the paths in the diff that have more than one value.
+fn find_conflicts(diff: &Diff, path: Path, conflicts: &mut Vec<Path>) {
+    match diff {
+        Diff::Map(map_diff) => {
+            for (key, diffs) in &map_diff.props {
+                let path = path.clone().key(key.clone());
+                if diffs.len() > 1 {
+                    conflicts.push(path.clone());
+                }
+                for diff in diffs.values() {
+                    find_conflicts(diff, path.clone(), conflicts)
+                }
+            }
+        }
+        Diff::Seq(seq_diff) => {
+            for (index, diffs) in &seq_diff.props {
+                let path = path.clone().index((*index).try_into().unwrap());
+                if diffs.len() > 1 {
+                    conflicts.push(path.clone());
+                }
+                for diff in diffs.values() {
+                    find_conflicts(diff, path.clone(), conflicts)
+                }
+            }
+        }
+        Diff::Unchanged(_) | Diff::Value(_) | Diff::Cursor(_) => {}
+    }
+}
+
+/// Build the changes that create `value` at `path`.
+///
+/// Objects are created empty and then filled in sorted key order so that the operations are the
+/// same wherever this runs.
+fn structure_changes(value: &Value, path: Path, changes: &mut Vec<LocalChange>) {
+    match value {
+        Value::Map(map) | Value::Table(map) => {
+            let mut keys = map.keys().collect::<Vec<_>>();
+            keys.sort();
+            for key in keys {
+                let value = &map[key];
+                let path = path.clone().key(key.clone());
+                changes.push(LocalChange::set(path.clone(), empty_like(value)));
+                structure_changes(value, path, changes)
+            }
+        }
+        Value::List(vec) => {
+            for (i, value) in vec.iter().enumerate() {
+                let path = path.clone().index(i.try_into().unwrap());
+                changes.push(LocalChange::insert(path.clone(), empty_like(value)));
+                structure_changes(value, path, changes)
+            }
+        }
+        Value::Text(graphemes) => {
+            for (i, grapheme) in graphemes.iter().enumerate() {
+                changes.push(LocalChange::insert(
+                    path.clone().index(i.try_into().unwrap()),
+                    Value::Primitive(Primitive::Str(grapheme.clone())),
+                ))
+            }
+        }
+        Value::Primitive(_) => {}
+    }
+}
+
+fn empty_like(value: &Value) -> Value {
+    match value {
+        Value::Map(_) => Value::Map(HashMap::new()),
+        Value::Table(_) => Value::Table(HashMap::new()),
+        Value::List(_) => Value::List(Vec::new()),
+        Value::Text(_) => Value::Text(Vec::new()),
+        Value::Primitive(p) => Value::Primitive(p.clone()),
+    }
 }
 
 #[cfg(test)]
@@ -330,4 +1091,652 @@ mod tests {
             doc.apply_patch(patch).unwrap();
         }
     }
+
+    #[test]
+    fn apply_remote_patch_updates_changed_fields() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            list: Vec<String>,
+            others: std::collections::HashMap<String, String>,
+            #[automergeable(representation = "Counter")]
+            a_counter: i64,
+            b: B,
+        }
+
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct B {
+            inner: u64,
+            other: String,
+        }
+
+        let mut doc1 = Document::<A, _>::new(Frontend::new());
+        let mut back1 = automerge::Backend::new();
+        let mut doc2 = Document::<A, _>::new(Frontend::new());
+        let mut back2 = automerge::Backend::new();
+
+        let mut sync = |change: Option<automerge_protocol::Change>,
+                        doc1: &mut Document<A, Frontend>,
+                        doc2: &mut Document<A, Frontend>| {
+            if let Some(change) = change {
+                let (patch, change) = back1.apply_local_change(change).unwrap();
+                doc1.apply_patch(patch).unwrap();
+                let patch = back2.apply_changes(vec![change]).unwrap();
+                doc2.apply_patch(patch).unwrap();
+            }
+        };
+
+        let ((), change) = doc1
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.list.push("hi".to_owned());
+                a.others.insert("a".to_owned(), "b".to_owned());
+                a.b.other = "other".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        sync(change, &mut doc1, &mut doc2);
+        assert_eq!(doc1.get(), doc2.get());
+
+        let ((), change) = doc1
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.a_counter += 2;
+                a.b.inner = 3;
+                a.others.remove("a");
+                a.list[0] = "there".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        sync(change, &mut doc1, &mut doc2);
+        assert_eq!(
+            doc2.get(),
+            &A {
+                list: vec!["there".to_owned()],
+                others: std::collections::HashMap::new(),
+                a_counter: 2,
+                b: B {
+                    inner: 3,
+                    other: "other".to_owned(),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn failed_change_restores_value() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            list: Vec<String>,
+            inner: u64,
+        }
+
+        let mut doc = Document::<A, _>::new(Frontend::new());
+        let snapshot = doc.snapshot();
+
+        let res = doc.change::<_, (), _>(|a| {
+            a.list.push("hi".to_owned());
+            a.inner = 5;
+            Err(std::fmt::Error)
+        });
+        assert!(matches!(res, Err(DocumentChangeError::ChangeError(_))));
+        assert_eq!(doc.get(), &A::default());
+        assert!(Arc::ptr_eq(&snapshot, &doc.snapshot()));
+    }
+
+    #[test]
+    fn panicking_change_leaves_value() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            list: Vec<String>,
+        }
+
+        let mut doc = Document::<A, _>::new(Frontend::new());
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            doc.change::<_, (), std::convert::Infallible>(|a| {
+                a.list.push("hi".to_owned());
+                panic!("change failed part way through")
+            })
+        }));
+        assert!(res.is_err());
+        assert_eq!(doc.get(), &A::default());
+    }
+
+    #[test]
+    fn unrepresentable_change_is_an_error() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            big: u128,
+            float: f64,
+        }
+
+        let mut doc = Document::<A, _>::new(Frontend::new());
+
+        let res = doc.change::<_, _, std::convert::Infallible>(|a| {
+            a.big = u128::MAX;
+            Ok(())
+        });
+        assert!(matches!(
+            res,
+            Err(DocumentChangeError::ToError(
+                crate::ToAutomergeError::OutOfRange { .. }
+            ))
+        ));
+        assert_eq!(doc.get(), &A::default());
+
+        let res = doc.change::<_, _, std::convert::Infallible>(|a| {
+            a.float = f64::NAN;
+            Ok(())
+        });
+        assert!(matches!(
+            res,
+            Err(DocumentChangeError::ToError(
+                crate::ToAutomergeError::NotANumber
+            ))
+        ));
+        assert_eq!(doc.get(), &A::default());
+    }
+
+    #[test]
+    fn preview_and_budget() {
+        let mut doc =
+            Document::<std::collections::HashMap<String, String>, _>::new(Frontend::new());
+
+        let ((), changes, report) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|map| {
+                map.insert("a".to_owned(), "b".to_owned());
+                map.insert("c".to_owned(), "d".to_owned());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(report.sets, 2);
+        assert_eq!(report.operations, 2);
+        assert!(doc.get().is_empty());
+
+        doc.set_change_budget(ChangeBudget {
+            max_operations: Some(1),
+            max_bytes: None,
+        });
+        let res = doc.change::<_, _, std::convert::Infallible>(|map| {
+            map.insert("a".to_owned(), "b".to_owned());
+            map.insert("c".to_owned(), "d".to_owned());
+            Ok(())
+        });
+        assert!(matches!(
+            res,
+            Err(DocumentChangeError::BudgetExceeded {
+                limit: BudgetLimit::Operations,
+                actual: 2,
+                allowed: 1,
+            })
+        ));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "change exceeded budget: 2 operations but at most 1 are allowed"
+        );
+        assert!(doc.get().is_empty());
+
+        doc.change::<_, _, std::convert::Infallible>(|map| {
+            map.insert("a".to_owned(), "b".to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(doc.get().len(), 1);
+    }
+
+    #[test]
+    fn bootstrapped_replicas_merge_nested_edits() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            b: B,
+            list: Vec<String>,
+        }
+
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct B {
+            first: u64,
+            second: u64,
+        }
+
+        let mut peers = Vec::new();
+        for i in 1..=2 {
+            let (mut doc, genesis) = Document::<A, _>::bootstrap(Frontend::new()).unwrap();
+            // changes would create their own objects until the genesis change is applied
+            assert!(matches!(
+                doc.change::<_, _, std::convert::Infallible>(|_| Ok(())),
+                Err(DocumentChangeError::GenesisNotApplied)
+            ));
+            let mut back = automerge::Backend::new();
+            let (patch, _) = back.apply_local_change(genesis.unwrap()).unwrap();
+            doc.apply_patch(patch).unwrap();
+
+            let ((), change) = doc
+                .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                    if i == 1 {
+                        a.b.first = 1;
+                    } else {
+                        a.b.second = 2;
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            let (patch, _) = back.apply_local_change(change.unwrap()).unwrap();
+            doc.apply_patch(patch).unwrap();
+            peers.push((doc, back));
+        }
+
+        let changes2 = peers[1]
+            .1
+            .get_changes(&[])
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let (doc1, back1) = &mut peers[0];
+        let patch = back1.apply_changes(changes2).unwrap();
+        doc1.apply_patch(patch).unwrap();
+
+        assert_eq!(
+            doc1.get(),
+            &A {
+                b: B {
+                    first: 1,
+                    second: 2
+                },
+                list: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn pending_changes_rebase_onto_remote_patches() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            mine: String,
+            theirs: String,
+        }
+
+        let mut doc1 = Document::<A, _>::new(Frontend::new());
+        doc1.set_track_pending(true);
+        let mut back1 = automerge::Backend::new();
+        let mut doc2 = Document::<A, _>::new(Frontend::new());
+        let mut back2 = automerge::Backend::new();
+
+        let ((), change) = doc2
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.theirs = "theirs".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        let (patch, remote) = back2.apply_local_change(change.unwrap()).unwrap();
+        doc2.apply_patch(patch).unwrap();
+
+        let ((), local) = doc1
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.mine = "mine".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(doc1.pending_changes().len(), 1);
+
+        // the remote change arrives before our local one is acknowledged
+        let patch = back1.apply_changes(vec![remote]).unwrap();
+        doc1.apply_patch(patch).unwrap();
+        let expected = A {
+            mine: "mine".to_owned(),
+            theirs: "theirs".to_owned(),
+        };
+        assert_eq!(doc1.get(), &expected);
+        assert_eq!(doc1.pending_changes().len(), 1);
+
+        let (patch, _) = back1.apply_local_change(local.unwrap()).unwrap();
+        doc1.apply_patch(patch).unwrap();
+        assert_eq!(doc1.get(), &expected);
+        assert_eq!(doc1.pending_changes().len(), 0);
+    }
+
+    #[test]
+    fn restore_pending_changes() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            name: String,
+        }
+
+        let mut untracked = Document::<A, _>::new(Frontend::new());
+        untracked
+            .change::<_, _, std::convert::Infallible>(|a| {
+                a.name = "untracked".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        assert!(untracked.pending_changes().is_empty());
+
+        let mut offline = Document::<A, _>::new(Frontend::new());
+        offline.set_track_pending(true);
+        offline
+            .change::<_, _, std::convert::Infallible>(|a| {
+                a.name = "offline".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        let persisted = offline
+            .pending_changes()
+            .iter()
+            .map(|p| PendingChange::new(p.change().clone(), p.before().clone(), p.after().clone()))
+            .collect::<Vec<_>>();
+        drop(offline);
+
+        // after a restart
+        let mut doc = Document::<A, _>::new(Frontend::new());
+        doc.restore_pending(persisted).unwrap();
+        assert_eq!(doc.get().name, "offline");
+        assert_eq!(doc.pending_changes().len(), 1);
+
+        let mut back = automerge::Backend::new();
+        let change = doc.pending_changes()[0].change().clone();
+        let (patch, _) = back.apply_local_change(change).unwrap();
+        doc.apply_patch(patch).unwrap();
+        assert_eq!(doc.get().name, "offline");
+        assert!(doc.pending_changes().is_empty());
+    }
+
+    #[test]
+    fn fork_and_merge() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            title: String,
+            body: String,
+        }
+
+        let mut doc = Document::<A, _>::with_backend(Frontend::new(), automerge::Backend::new());
+        doc.change::<_, _, automerge::InvalidChangeRequest>(|a| {
+            a.title = "title".to_owned();
+            Ok(())
+        })
+        .unwrap();
+
+        let mut draft = doc.fork().unwrap();
+        assert_eq!(draft.get(), doc.get());
+        draft
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.body = "draft body".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(doc.get().body, "");
+
+        let report = doc.merge(&draft).unwrap();
+        assert_eq!(report.changes, 1);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(doc.get(), draft.get());
+
+        // nothing new to merge the second time around
+        let report = doc.merge(&draft).unwrap();
+        assert_eq!(report.changes, 0);
+    }
+
+    #[test]
+    fn rooted_list_document() {
+        let mut doc = Document::<crate::Rooted<Vec<String>>, _>::with_backend(
+            Frontend::new(),
+            automerge::Backend::new(),
+        );
+        doc.change::<_, _, automerge::InvalidChangeRequest>(|list| {
+            list.push("a".to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(doc.get().0, vec!["a".to_owned()]);
+        if let Value::Map(root) = &*doc.snapshot() {
+            assert_eq!(
+                root.get(crate::Rooted::<()>::KEY),
+                Some(&Value::List(vec![Value::Primitive(Primitive::Str(
+                    "a".into()
+                ))]))
+            );
+        } else {
+            panic!("root should be a map")
+        }
+    }
+
+    #[test]
+    fn concurrent_set_inserts_merge() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct A {
+            tags: std::collections::BTreeSet<String>,
+        }
+
+        let mut doc = Document::<A, _>::with_backend(Frontend::new(), automerge::Backend::new());
+        doc.change::<_, _, automerge::InvalidChangeRequest>(|a| {
+            a.tags.insert("old".to_owned());
+            Ok(())
+        })
+        .unwrap();
+
+        let mut other = doc.fork().unwrap();
+        other
+            .change::<_, _, automerge::InvalidChangeRequest>(|a| {
+                a.tags.insert("new".to_owned());
+                a.tags.remove("old");
+                Ok(())
+            })
+            .unwrap();
+        doc.change::<_, _, automerge::InvalidChangeRequest>(|a| {
+            a.tags.insert("new".to_owned());
+            Ok(())
+        })
+        .unwrap();
+
+        doc.merge(&other).unwrap();
+        assert_eq!(
+            doc.get().tags.iter().collect::<Vec<_>>(),
+            vec![&"new".to_owned()]
+        );
+    }
+
+    #[test]
+    fn text_edits_are_splices() {
+        #[derive(crate::Automergeable, Debug, Clone, Default)]
+        #[automergeable(crate_path = "crate")]
+        struct Note {
+            body: crate::Text,
+        }
+
+        let mut doc = Document::<Note, _>::with_backend(Frontend::new(), automerge::Backend::new());
+        doc.change::<_, _, std::convert::Infallible>(|note| {
+            note.body.push_str("hello world");
+            Ok(())
+        })
+        .unwrap();
+
+        let ((), changes, _) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|note| {
+                note.body.insert(5, ",");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+
+        // the typed index is kept even where comparing would find a later one
+        let ((), changes, _) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|note| {
+                note.body.insert(2, "l");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![LocalChange::insert(
+                Path::root().key("body").index(2),
+                Value::Primitive(Primitive::Str("l".into()))
+            )]
+        );
+
+        doc.change::<_, _, std::convert::Infallible>(|note| {
+            note.body.splice(0, 5, "goodbye");
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(doc.get().body, "goodbye world");
+        assert!(doc.get().body.splices().is_empty());
+    }
+
+    #[test]
+    fn remote_text_edits_apply_in_place() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct Note {
+            body: crate::Text,
+        }
+
+        let mut doc1 = Document::<Note, _>::new(Frontend::new());
+        let mut back1 = automerge::Backend::new();
+        let mut doc2 = Document::<Note, _>::new(Frontend::new());
+        let mut back2 = automerge::Backend::new();
+
+        for edit in [
+            (|note: &mut Note| note.body.push_str("hello")) as fn(&mut Note),
+            |note| note.body.insert(2, "l"),
+            |note| note.body.splice(0, 1, "j"),
+            |note| note.body.delete(5, 1),
+        ] {
+            let ((), change) = doc1
+                .change::<_, _, std::convert::Infallible>(|note| {
+                    edit(note);
+                    Ok(())
+                })
+                .unwrap();
+            let (patch, change) = back1.apply_local_change(change.unwrap()).unwrap();
+            doc1.apply_patch(patch).unwrap();
+            let patch = back2.apply_changes(vec![change]).unwrap();
+            doc2.apply_patch(patch).unwrap();
+            assert_eq!(doc1.get(), doc2.get());
+        }
+        assert_eq!(doc2.get().body, "jelll");
+    }
+
+    #[test]
+    fn concurrent_counter_increments_add_up() {
+        #[derive(crate::Automergeable, Debug, Clone, Default)]
+        #[automergeable(crate_path = "crate")]
+        struct Votes {
+            by_option: HashMap<String, crate::Counter>,
+            last_vote: Option<crate::Timestamp>,
+        }
+
+        let mut doc =
+            Document::<Votes, _>::with_backend(Frontend::new(), automerge::Backend::new());
+        doc.change::<_, _, std::convert::Infallible>(|votes| {
+            votes
+                .by_option
+                .insert("a".to_owned(), crate::Counter::new(1));
+            Ok(())
+        })
+        .unwrap();
+
+        let mut other = doc.fork().unwrap();
+        other
+            .change::<_, _, std::convert::Infallible>(|votes| {
+                votes.by_option.get_mut("a").unwrap().increment(2);
+                votes.last_vote = Some(crate::Timestamp::from_millis(10));
+                Ok(())
+            })
+            .unwrap();
+        doc.change::<_, _, std::convert::Infallible>(|votes| {
+            *votes.by_option.get_mut("a").unwrap() += 3;
+            Ok(())
+        })
+        .unwrap();
+
+        doc.merge(&other).unwrap();
+        assert_eq!(doc.get().by_option["a"].value(), 6);
+        assert_eq!(doc.get().last_vote, Some(crate::Timestamp::from_millis(10)));
+    }
+
+    #[test]
+    fn shared_values_are_skipped() {
+        #[derive(crate::Automergeable, Debug, Clone, Default, PartialEq)]
+        #[automergeable(crate_path = "crate")]
+        struct Log {
+            entries: Arc<Vec<String>>,
+            edits: u64,
+        }
+
+        let mut doc = Document::<Log, _>::new(Frontend::new());
+        doc.change::<_, _, std::convert::Infallible>(|log| {
+            log.entries = Arc::new(vec!["a".to_owned(), "b".to_owned()]);
+            Ok(())
+        })
+        .unwrap();
+
+        // the entries are still shared with the old value so aren't converted
+        let old = doc.get().clone();
+        let mut new = old.clone();
+        new.edits += 1;
+        let mut changed = ChangedValues::default();
+        crate::TryToAutomerge::try_to_automerge_changed(&new, &old, &mut Vec::new(), &mut changed)
+            .unwrap();
+        assert_eq!(
+            changed.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            vec![&[crate::PathSegment::Key("edits".to_owned())][..]]
+        );
+
+        let ((), changes, _) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|log| {
+                Arc::make_mut(&mut log.entries)[0] = "z".to_owned();
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![LocalChange::set(
+                Path::root().key("entries").index(0),
+                Value::Primitive(Primitive::Str("z".into()))
+            )]
+        );
+
+        doc.change::<_, _, std::convert::Infallible>(|log| {
+            Arc::make_mut(&mut log.entries).push("c".to_owned());
+            log.edits += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(*doc.snapshot(), doc.get_root().unwrap());
+        assert_eq!(doc.get().entries.len(), 3);
+    }
+
+    #[test]
+    fn removed_map_entries_are_deleted() {
+        let mut doc = Document::<HashMap<String, String>, _>::new(Frontend::new());
+        doc.change::<_, _, std::convert::Infallible>(|map| {
+            map.insert("a".to_owned(), "1".to_owned());
+            map.insert("b".to_owned(), "2".to_owned());
+            Ok(())
+        })
+        .unwrap();
+
+        let ((), changes, _) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|map| {
+                map.remove("a");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(changes, vec![LocalChange::delete(Path::root().key("a"))]);
+
+        doc.change::<_, _, std::convert::Infallible>(|map| {
+            map.remove("a");
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(*doc.snapshot(), doc.get_root().unwrap());
+    }
 }
@@ -101,21 +101,61 @@
 //! # }
 //! ```
 
+mod builder;
 mod diff;
 mod document;
+mod rebase;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod threaded;
 
-#[doc(hidden)]
-pub use automergeable_traits::Text;
-pub use automergeable_traits::{Automergeable, FromAutomerge, FromAutomergeError, ToAutomerge};
-pub use diff::{diff_values, diff_with_path};
-pub use document::{Document, DocumentChangeError};
+pub use automergeable_traits::{
+    expect_value, json, ApplyPatch, AsTimestamp, Automergeable, ChangedValues, Counter, ErrorPath,
+    FromAutomerge, FromAutomergeError, FromAutomergeRef, MapKey, PathSegment, RootAutomergeable,
+    Rooted, Text, TextSplice, TextSplices, Timestamp, ToAutomerge, ToAutomergeError,
+    TryToAutomerge, ValueKind,
+};
+pub use builder::{DocumentBuildError, DocumentBuilder};
+pub use diff::{
+    diff_changed, diff_values, diff_with_options, diff_with_path, diff_with_splices, DiffOptions,
+    DiffReport,
+};
+pub use document::{
+    ApplyPatchError, BudgetLimit, ChangeBudget, Document, DocumentChangeError, MergeError,
+    MergeReport, PendingChange, GENESIS_ACTOR,
+};
+pub use threaded::{ThreadedDocument, ThreadedDocumentError};
+
+// reading and writing typed values on documents on the newer automerge engine, through views
+#[cfg(feature = "automergeable-view")]
+pub use automergeable_view::{FromView, ReadView, Reconcile, ReconcileError, Slot};
 
 /// Derive macro magic
 extern crate automergeable_derive;
-pub use automergeable_derive::{Automergeable, FromAutomerge, ToAutomerge};
+pub use automergeable_derive::{
+    ApplyPatch, Automergeable, FromAutomerge, FromAutomergeRef, MapKey, RootAutomergeable,
+    ToAutomerge, TryToAutomerge,
+};
+#[cfg(feature = "automergeable-view")]
+pub use automergeable_derive::{FromView, Reconcile};
 
 /// needed for derive macro inner workings
 #[doc(hidden)]
 pub mod unicode_segmentation {
     pub use unicode_segmentation::UnicodeSegmentation;
 }
+
+/// needed for derive macro inner workings
+#[doc(hidden)]
+pub mod patch {
+    pub use automerge_protocol::Diff;
+    pub use automergeable_traits::resolve_diff;
+}
+
+/// needed for derive macro inner workings
+#[cfg(feature = "automergeable-view")]
+#[doc(hidden)]
+pub mod reconcile {
+    pub use automerge::transaction::Transaction;
+    pub use automergeable_view::{Fields, Items, MutableView, Variant, View};
+}
@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+
+use automerge::{Primitive, Value};
+use automerge_protocol::{Diff, DiffEdit, MapType, ScalarValue, SequenceType};
+use smol_str::SmolStr;
+
+/// Apply a patch diff to a value, as the frontend would.
+///
+/// Conflicts are resolved by taking the value from the greatest operation.
+pub fn apply_diff(value: &mut Value, diff: &Diff) {
+    match diff {
+        Diff::Map(map_diff) => {
+            let is_table = map_diff.obj_type == MapType::Table;
+            match (value, is_table) {
+                (Value::Map(map), false) | (Value::Table(map), true) => {
+                    for (key, diffs) in &map_diff.props {
+                        let winner = diffs.iter().max_by_key(|(op, _)| *op).map(|(_, d)| d);
+                        if let Some(diff) = winner {
+                            let entry = map
+                                .entry(SmolStr::new(key))
+                                .or_insert(Value::Primitive(Primitive::Null));
+                            apply_diff(entry, diff)
+                        } else {
+                            map.remove(key.as_str());
+                        }
+                    }
+                }
+                (value, _) => {
+                    // a new object
+                    *value = if is_table {
+                        Value::Table(HashMap::new())
+                    } else {
+                        Value::Map(HashMap::new())
+                    };
+                    apply_diff(value, diff)
+                }
+            }
+        }
+        Diff::Seq(seq_diff) => {
+            let is_text = seq_diff.obj_type == SequenceType::Text;
+            match (value, is_text) {
+                (Value::List(list), false) => {
+                    for edit in &seq_diff.edits {
+                        match edit {
+                            DiffEdit::Insert { index, .. } => {
+                                list.insert(*index, Value::Primitive(Primitive::Null))
+                            }
+                            DiffEdit::Remove { index } => {
+                                list.remove(*index);
+                            }
+                        }
+                    }
+                    for (index, diffs) in &seq_diff.props {
+                        let winner = diffs.iter().max_by_key(|(op, _)| *op).map(|(_, d)| d);
+                        if let (Some(item), Some(diff)) = (list.get_mut(*index), winner) {
+                            apply_diff(item, diff)
+                        }
+                    }
+                }
+                (Value::Text(graphemes), true) => {
+                    for edit in &seq_diff.edits {
+                        match edit {
+                            DiffEdit::Insert { index, .. } => {
+                                graphemes.insert(*index, SmolStr::default())
+                            }
+                            DiffEdit::Remove { index } => {
+                                graphemes.remove(*index);
+                            }
+                        }
+                    }
+                    for (index, diffs) in &seq_diff.props {
+                        let winner = diffs.iter().max_by_key(|(op, _)| *op).map(|(_, d)| d);
+                        if let (Some(grapheme), Some(Diff::Value(ScalarValue::Str(s)))) =
+                            (graphemes.get_mut(*index), winner)
+                        {
+                            *grapheme = s.clone()
+                        }
+                    }
+                }
+                (value, _) => {
+                    // a new object
+                    *value = if is_text {
+                        Value::Text(Vec::new())
+                    } else {
+                        Value::List(Vec::new())
+                    };
+                    apply_diff(value, diff)
+                }
+            }
+        }
+        Diff::Value(scalar) => *value = Value::Primitive(primitive(scalar)),
+        // cursors aren't tracked in the typed value and unchanged objects have nothing to apply
+        Diff::Unchanged(_) | Diff::Cursor(_) => {}
+    }
+}
+
+fn primitive(scalar: &ScalarValue) -> Primitive {
+    match scalar {
+        ScalarValue::Bytes(b) => Primitive::Bytes(b.clone()),
+        ScalarValue::Str(s) => Primitive::Str(s.clone()),
+        ScalarValue::Int(i) => Primitive::Int(*i),
+        ScalarValue::Uint(u) => Primitive::Uint(*u),
+        ScalarValue::F64(f) => Primitive::F64(*f),
+        ScalarValue::Counter(c) => Primitive::Counter(*c),
+        ScalarValue::Timestamp(t) => Primitive::Timestamp(*t),
+        ScalarValue::Boolean(b) => Primitive::Boolean(*b),
+        ScalarValue::Null => Primitive::Null,
+    }
+}
+
+/// Rebase the change from `base` to `ours` onto `theirs`.
+///
+/// Maps are merged key by key, and lists and text by index. Where both sides changed the same
+/// part our version wins as it is the most recent edit.
+pub fn merge(base: &Value, ours: &Value, theirs: &Value) -> Value {
+    if ours == base {
+        return theirs.clone();
+    }
+    if theirs == base {
+        return ours.clone();
+    }
+    match (base, ours, theirs) {
+        (Value::Map(base), Value::Map(ours), Value::Map(theirs)) => {
+            Value::Map(merge_maps(base, ours, theirs))
+        }
+        (Value::Table(base), Value::Table(ours), Value::Table(theirs)) => {
+            Value::Table(merge_maps(base, ours, theirs))
+        }
+        (Value::List(base), Value::List(ours), Value::List(theirs)) => {
+            Value::List(merge_seqs(base, ours, theirs, merge))
+        }
+        (Value::Text(base), Value::Text(ours), Value::Text(theirs)) => {
+            Value::Text(merge_seqs(base, ours, theirs, |_, ours, _| ours.clone()))
+        }
+        _ => ours.clone(),
+    }
+}
+
+/// The range of `base` that was replaced to get `edited`, as the start, the end in `base` and the
+/// end in `edited`.
+///
+/// This is everything between the items that are the same at the start and end.
+fn edited_range<T: PartialEq>(base: &[T], edited: &[T]) -> (usize, usize, usize) {
+    let start = base
+        .iter()
+        .zip(edited)
+        .take_while(|(base, edited)| base == edited)
+        .count();
+    let end = base[start..]
+        .iter()
+        .rev()
+        .zip(edited[start..].iter().rev())
+        .take_while(|(base, edited)| base == edited)
+        .count();
+    (start, base.len() - end, edited.len() - end)
+}
+
+/// Rebase our edit of the sequence `base` onto theirs, by index.
+///
+/// Edits to separate ranges are both kept, with ours moved along by any items they inserted or
+/// removed before it, and items inserted at the same index go after theirs. Where the edits
+/// overlap ours wins, except that items both sides changed in place are merged with
+/// `merge_item`.
+fn merge_seqs<T, M>(base: &[T], ours: &[T], theirs: &[T], merge_item: M) -> Vec<T>
+where
+    T: Clone + PartialEq,
+    M: Fn(&T, &T, &T) -> T,
+{
+    let (our_start, our_base_end, our_end) = edited_range(base, ours);
+    let (their_start, their_base_end, their_end) = edited_range(base, theirs);
+    let mut merged = theirs.to_vec();
+    let same_insert_point =
+        our_start == our_base_end && their_start == their_base_end && our_start == their_start;
+    if our_base_end <= their_start && !same_insert_point {
+        // ours is before theirs so is at the same index
+        merged.splice(
+            our_start..our_base_end,
+            ours[our_start..our_end].iter().cloned(),
+        );
+    } else if their_base_end <= our_start {
+        // ours is after theirs so moves along by what they inserted or removed
+        let shift = |index: usize| index - their_base_end + their_end;
+        merged.splice(
+            shift(our_start)..shift(our_base_end),
+            ours[our_start..our_end].iter().cloned(),
+        );
+    } else if our_start == their_start
+        && our_base_end == their_base_end
+        && our_end == our_base_end
+        && their_end == their_base_end
+    {
+        // the same items were changed in place on both sides
+        for index in our_start..our_base_end {
+            merged[index] = merge_item(&base[index], &ours[index], &theirs[index]);
+        }
+    } else {
+        // overlapping edits, so take our version of everything either side touched
+        let start = our_start.min(their_start);
+        let base_end = our_base_end.max(their_base_end);
+        merged.splice(
+            start..base_end - their_base_end + their_end,
+            ours[start..base_end - our_base_end + our_end]
+                .iter()
+                .cloned(),
+        );
+    }
+    merged
+}
+
+fn merge_maps(
+    base: &HashMap<SmolStr, Value>,
+    ours: &HashMap<SmolStr, Value>,
+    theirs: &HashMap<SmolStr, Value>,
+) -> HashMap<SmolStr, Value> {
+    let keys = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect::<HashSet<_>>();
+    let mut merged = HashMap::with_capacity(keys.len());
+    for key in keys {
+        let value = match (base.get(key), ours.get(key), theirs.get(key)) {
+            (_, None, None) => None,
+            (None, Some(ours), _) => Some(ours.clone()),
+            (None, None, Some(theirs)) => Some(theirs.clone()),
+            // we removed it, keep it only if they changed it
+            (Some(base), None, Some(theirs)) => (theirs != base).then(|| theirs.clone()),
+            // they removed it, keep it only if we changed it
+            (Some(base), Some(ours), None) => (ours != base).then(|| ours.clone()),
+            (Some(base), Some(ours), Some(theirs)) => Some(merge(base, ours, theirs)),
+        };
+        if let Some(value) = value {
+            merged.insert(key.clone(), value);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use super::*;
+
+    fn str(s: &str) -> Value {
+        Value::Primitive(Primitive::Str(s.into()))
+    }
+
+    #[test]
+    fn merge_disjoint_keys() {
+        let base = Value::Map(hashmap! {"a".into() => str("a"), "b".into() => str("b")});
+        let ours = Value::Map(hashmap! {"a".into() => str("ours"), "b".into() => str("b")});
+        let theirs = Value::Map(hashmap! {"a".into() => str("a"), "c".into() => str("theirs")});
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            Value::Map(hashmap! {"a".into() => str("ours"), "c".into() => str("theirs")})
+        );
+    }
+
+    #[test]
+    fn merge_concurrent_list_inserts() {
+        let base = Value::List(vec![str("a"), str("b")]);
+        let ours = Value::List(vec![str("a"), str("ours"), str("b")]);
+        let theirs = Value::List(vec![str("a"), str("b"), str("theirs")]);
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            Value::List(vec![str("a"), str("ours"), str("b"), str("theirs")])
+        );
+
+        // inserts at the same index keep both, ours last
+        let base = Value::List(vec![str("a")]);
+        let ours = Value::List(vec![str("a"), str("ours")]);
+        let theirs = Value::List(vec![str("a"), str("theirs")]);
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            Value::List(vec![str("a"), str("theirs"), str("ours")])
+        );
+    }
+
+    #[test]
+    fn merge_list_items_in_place() {
+        let item =
+            |a: &str, b: &str| Value::Map(hashmap! {"a".into() => str(a), "b".into() => str(b)});
+        let base = Value::List(vec![item("a", "b"), item("a", "b")]);
+        let ours = Value::List(vec![item("ours", "b"), item("a", "b")]);
+        let theirs = Value::List(vec![item("a", "theirs"), item("a", "b")]);
+        assert_eq!(
+            merge(&base, &ours, &theirs),
+            Value::List(vec![item("ours", "theirs"), item("a", "b")])
+        );
+    }
+
+    #[test]
+    fn merge_text() {
+        let text = |s: &str| Value::Text(s.chars().map(|c| c.to_string().into()).collect());
+        assert_eq!(
+            merge(&text("hello"), &text("hello world"), &text("Hello")),
+            text("Hello world")
+        );
+        // overlapping edits take ours
+        assert_eq!(
+            merge(&text("hello"), &text("help"), &text("hold")),
+            text("help")
+        );
+    }
+
+    #[test]
+    fn merge_conflicting_keys_prefers_ours() {
+        let base = Value::Map(hashmap! {"a".into() => str("a")});
+        let ours = Value::Map(hashmap! {"a".into() => str("ours")});
+        let theirs = Value::Map(hashmap! {"a".into() => str("theirs")});
+        assert_eq!(merge(&base, &ours, &theirs), ours);
+    }
+}
@@ -0,0 +1,248 @@
+//! Convert between serde types and automerge values.
+//!
+//! This is for types that implement [`Serialize`] and [`Deserialize`] but can't derive
+//! [`Automergeable`](crate::Automergeable), such as those from other crates.
+//!
+//! ```rust
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct Task {
+//!     title: String,
+//!     likes: automergeable::Counter,
+//! }
+//!
+//! let task = Task {
+//!     title: "write docs".to_owned(),
+//!     likes: automergeable::Counter::new(3),
+//! };
+//! let value = automergeable::serde::to_value(&task).unwrap();
+//! assert_eq!(automergeable::serde::from_value::<Task>(&value).unwrap(), task);
+//! ```
+//!
+//! Text, counters and timestamps have no serde equivalent, so the [`Text`](crate::Text),
+//! [`Counter`](crate::Counter) and [`Timestamp`](crate::Timestamp) types serialize as newtypes
+//! with reserved names that are stored as those automerge values.
+
+use std::ops::{Deref, DerefMut};
+
+use ::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use automerge::Value;
+use automergeable_traits::marker::{COUNTER, TEXT, TIMESTAMP};
+
+use crate::{
+    expect_value, ApplyPatch, FromAutomerge, FromAutomergeError, ToAutomerge, ToAutomergeError,
+};
+
+mod de;
+mod ser;
+
+pub use de::Deserializer;
+pub use ser::Serializer;
+
+/// An error converting between serde types and automerge values.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A custom error from a serde implementation.
+    #[error("{0}")]
+    Message(String),
+    /// Map keys have to be converted to strings.
+    #[error("map keys must be strings, chars, bools or integers")]
+    KeyMustBeAString,
+    /// The number doesn't fit into an automerge int or uint.
+    #[error("{0} is out of range for automerge")]
+    OutOfRange(String),
+    /// A marker newtype didn't contain the type it marks.
+    #[error("{marker} must contain {expected}")]
+    InvalidMarker {
+        /// The name of the marker.
+        marker: &'static str,
+        /// What it needs to contain.
+        expected: &'static str,
+    },
+    /// Cursors have no serde representation.
+    #[error("cursors can't be deserialized")]
+    Cursor,
+}
+
+impl ::serde::ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl ::serde::de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+/// Serialize a value into an automerge value.
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(Serializer)
+}
+
+/// Deserialize a value from an automerge value.
+pub fn from_value<T>(value: &Value) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Use any serde type as an automergeable one.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use automergeable::{serde::AsAutomerge, FromAutomerge, ToAutomerge};
+/// let mut map = HashMap::new();
+/// map.insert("a".to_owned(), vec![1, 2, 3]);
+/// let value = AsAutomerge(map).to_automerge();
+/// let AsAutomerge(map) = AsAutomerge::<HashMap<String, Vec<u8>>>::from_automerge(&value).unwrap();
+/// assert_eq!(map["a"], vec![1, 2, 3]);
+/// ```
+///
+/// To use one as the root of a [`Document`](crate::Document) wrap it in a
+/// [`Rooted`](crate::Rooted).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AsAutomerge<T>(pub T);
+
+impl<T> Deref for AsAutomerge<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for AsAutomerge<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> ToAutomerge for AsAutomerge<T>
+where
+    T: Serialize,
+{
+    fn to_automerge(&self) -> Value {
+        expect_value(self.to_automerge_checked())
+    }
+
+    fn to_automerge_checked(&self) -> Result<Value, ToAutomergeError> {
+        to_value(&self.0).map_err(|e| ToAutomergeError::Unknown(Box::new(e)))
+    }
+}
+
+impl<T> FromAutomerge for AsAutomerge<T>
+where
+    T: DeserializeOwned,
+{
+    fn from_automerge(value: &Value) -> Result<Self, FromAutomergeError> {
+        from_value(value)
+            .map(Self)
+            .map_err(|e| FromAutomergeError::Unknown(Box::new(e)))
+    }
+}
+
+impl<T> ApplyPatch for AsAutomerge<T> where T: DeserializeOwned {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use automerge::Primitive;
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::{Counter, Text, Timestamp};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Status {
+        Todo,
+        Done { at: Timestamp },
+        Blocked(String),
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Task {
+        title: Text,
+        likes: Counter,
+        status: Status,
+        tags: Vec<String>,
+        ids: BTreeMap<u32, bool>,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn roundtrip_with_markers() {
+        let task = Task {
+            title: Text::from("hi"),
+            likes: Counter::new(2),
+            status: Status::Done {
+                at: Timestamp::from_millis(10),
+            },
+            tags: vec!["a".to_owned()],
+            ids: vec![(1, true)].into_iter().collect(),
+            note: None,
+        };
+        let value = to_value(&task).unwrap();
+        assert_eq!(
+            value,
+            Value::Map(hashmap! {
+                "title".into() => Value::Text(vec!["h".into(), "i".into()]),
+                "likes".into() => Value::Primitive(Primitive::Counter(2)),
+                "status".into() => Value::Map(hashmap! {
+                    "Done".into() => Value::Map(hashmap! {
+                        "at".into() => Value::Primitive(Primitive::Timestamp(10)),
+                    }),
+                }),
+                "tags".into() => Value::List(vec![Value::Primitive(Primitive::Str("a".into()))]),
+                "ids".into() => Value::Map(hashmap! {
+                    "1".into() => Value::Primitive(Primitive::Boolean(true)),
+                }),
+                "note".into() => Value::Primitive(Primitive::Null),
+            })
+        );
+        assert_eq!(from_value::<Task>(&value).unwrap(), task);
+    }
+
+    #[test]
+    fn markers_match_derived_representation() {
+        let text = Text::from("hi");
+        assert_eq!(to_value(&text).unwrap(), text.to_automerge());
+        let counter = Counter::new(2);
+        assert_eq!(to_value(&counter).unwrap(), counter.to_automerge());
+        let timestamp = Timestamp::from_millis(10);
+        assert_eq!(to_value(&timestamp).unwrap(), timestamp.to_automerge());
+        assert_eq!(
+            from_value::<Counter>(&counter.to_automerge()).unwrap(),
+            counter
+        );
+    }
+
+    #[test]
+    fn enums_match_derived_representation() {
+        assert_eq!(
+            to_value(&Status::Todo).unwrap(),
+            Value::Primitive(Primitive::Str("Todo".into()))
+        );
+        let blocked = Status::Blocked("reason".to_owned());
+        let value = to_value(&blocked).unwrap();
+        assert_eq!(from_value::<Status>(&value).unwrap(), blocked);
+    }
+
+    #[test]
+    fn invalid_marker_is_an_error() {
+        #[derive(Serialize)]
+        #[serde(rename = "$automergeable::Counter")]
+        struct BadCounter(String);
+
+        assert!(matches!(
+            to_value(&BadCounter("a".to_owned())),
+            Err(Error::InvalidMarker { .. })
+        ));
+    }
+}
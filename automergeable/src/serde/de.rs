@@ -0,0 +1,316 @@
+use ::serde::{
+    de::{self, IntoDeserializer, Visitor},
+    forward_to_deserialize_any,
+};
+use automerge::{Primitive, Value};
+use smol_str::SmolStr;
+
+use super::{Error, COUNTER, TEXT, TIMESTAMP};
+
+/// A serde deserializer reading from an automerge value.
+///
+/// This accepts the representations produced by the [`Serializer`](super::Serializer).
+#[derive(Debug, Clone, Copy)]
+pub struct Deserializer<'de> {
+    value: &'de Value,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Create a deserializer for the given value.
+    pub const fn new(value: &'de Value) -> Self {
+        Self { value }
+    }
+
+    fn invalid_type(&self, expected: &dyn de::Expected) -> Error {
+        let unexpected = match self.value {
+            Value::Map(_) | Value::Table(_) => de::Unexpected::Map,
+            Value::List(_) => de::Unexpected::Seq,
+            Value::Text(_) => de::Unexpected::Other("text"),
+            Value::Primitive(p) => match p {
+                Primitive::Bytes(b) => de::Unexpected::Bytes(b),
+                Primitive::Str(s) => de::Unexpected::Str(s),
+                Primitive::Int(i) => de::Unexpected::Signed(*i),
+                Primitive::Uint(u) => de::Unexpected::Unsigned(*u),
+                Primitive::F64(f) => de::Unexpected::Float(*f),
+                Primitive::Counter(_) => de::Unexpected::Other("counter"),
+                Primitive::Timestamp(_) => de::Unexpected::Other("timestamp"),
+                Primitive::Boolean(b) => de::Unexpected::Bool(*b),
+                Primitive::Cursor(_) => de::Unexpected::Other("cursor"),
+                Primitive::Null => de::Unexpected::Unit,
+            },
+        };
+        de::Error::invalid_type(unexpected, expected)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Map(map) | Value::Table(map) => visitor.visit_map(MapAccess {
+                entries: map.iter(),
+                value: None,
+            }),
+            Value::List(list) => visitor.visit_seq(SeqAccess { items: list.iter() }),
+            Value::Text(graphemes) => visitor.visit_string(graphemes.concat()),
+            Value::Primitive(p) => match p {
+                Primitive::Bytes(b) => visitor.visit_borrowed_bytes(b),
+                Primitive::Str(s) => visitor.visit_borrowed_str(s),
+                Primitive::Int(i) | Primitive::Counter(i) | Primitive::Timestamp(i) => {
+                    visitor.visit_i64(*i)
+                }
+                Primitive::Uint(u) => visitor.visit_u64(*u),
+                Primitive::F64(f) => visitor.visit_f64(*f),
+                Primitive::Boolean(b) => visitor.visit_bool(*b),
+                Primitive::Cursor(_) => Err(Error::Cursor),
+                Primitive::Null => visitor.visit_unit(),
+            },
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Primitive(Primitive::Null) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match (name, self.value) {
+            (TEXT, Value::Text(graphemes)) => {
+                visitor.visit_newtype_struct(graphemes.concat().into_deserializer())
+            }
+            (COUNTER, Value::Primitive(Primitive::Counter(i)))
+            | (TIMESTAMP, Value::Primitive(Primitive::Timestamp(i))) => {
+                visitor.visit_newtype_struct((*i).into_deserializer())
+            }
+            (TEXT, _) => Err(self.invalid_type(&"text")),
+            (COUNTER, _) => Err(self.invalid_type(&"a counter")),
+            (TIMESTAMP, _) => Err(self.invalid_type(&"a timestamp")),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            Value::Primitive(Primitive::Str(variant)) => {
+                visitor.visit_enum(variant.as_str().into_deserializer())
+            }
+            Value::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().expect("map has one entry");
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(self.invalid_type(&"a string or a map with one entry")),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqAccess<'de> {
+    items: std::slice::Iter<'de, Value>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        self.items
+            .next()
+            .map(|item| seed.deserialize(Deserializer::new(item)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess<'de> {
+    entries: std::collections::hash_map::Iter<'de, SmolStr, Value>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(KeyDeserializer(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::Message("value requested before its key".to_owned()))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de SmolStr,
+    value: &'de Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Deserializer<'de>), Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Deserializes map keys, parsing them when another type is requested.
+struct KeyDeserializer<'de>(&'de str);
+
+macro_rules! parse_key {
+    ( $( $method:ident => $visit:ident ),* $(,)? ) => {
+        $(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            match self.0.parse() {
+                Ok(v) => visitor.$visit(v),
+                Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(self.0), &visitor)),
+            }
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    parse_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        f32 f64 str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
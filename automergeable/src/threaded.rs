@@ -0,0 +1,300 @@
+use std::{
+    error::Error,
+    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    thread::JoinHandle,
+};
+
+use automerge::{Backend, Frontend, LocalChange};
+use automerge_backend::AutomergeError;
+use automerge_protocol::Patch;
+
+use crate::{
+    document::ApplyPatchError, DiffReport, Document, DocumentChangeError, RootAutomergeable,
+};
+
+/// An error from a [`ThreadedDocument`].
+#[derive(Debug, thiserror::Error)]
+pub enum ThreadedDocumentError<E: Error = std::convert::Infallible> {
+    /// The change to the document failed.
+    #[error("change error: {0}")]
+    Change(#[from] DocumentChangeError<E>),
+    /// The backend failed to apply some changes.
+    ///
+    /// If it was a local change then the document is reset to the backend's state, dropping any
+    /// pending changes.
+    #[error("backend error: {0}")]
+    Backend(#[from] AutomergeError),
+    /// The patch from the backend couldn't be applied to the document.
+    #[error(transparent)]
+    ApplyPatch(#[from] ApplyPatchError<automerge_frontend::InvalidPatch>),
+    /// The backend thread has stopped.
+    #[error("backend thread disconnected")]
+    Disconnected,
+}
+
+enum Request {
+    Local(automerge_protocol::Change),
+    Remote(Vec<automerge::Change>),
+}
+
+enum Response {
+    Local((Patch, automerge::Change)),
+    /// A local change was rejected, along with the backend's state to reset the document to.
+    Rejected(AutomergeError, Result<Patch, AutomergeError>),
+    Remote(Result<Patch, AutomergeError>),
+}
+
+/// A typed document whose backend runs on a separate thread.
+///
+/// Local changes are made to the typed value immediately and sent to the backend, with the
+/// resulting patches being applied when they are processed with
+/// [`process_patches`](Self::process_patches) or [`flush`](Self::flush).
+#[derive(Debug)]
+pub struct ThreadedDocument<T>
+where
+    T: RootAutomergeable,
+{
+    document: Document<T, Frontend>,
+    requests: Option<Sender<Request>>,
+    responses: Receiver<Response>,
+    handle: Option<JoinHandle<()>>,
+    /// Requests that the backend hasn't responded to yet.
+    in_flight: usize,
+    /// Encoded local changes, ready to send to other peers.
+    outgoing: Vec<automerge::Change>,
+}
+
+impl<T> ThreadedDocument<T>
+where
+    T: RootAutomergeable,
+{
+    /// Construct a new document with a fresh backend.
+    pub fn new(frontend: Frontend) -> Self {
+        Self::with_backend(frontend, Backend::new())
+    }
+
+    /// Construct a new document, moving the given backend onto its own thread.
+    pub fn with_backend(frontend: Frontend, mut backend: Backend) -> Self {
+        let (requests, worker_requests) = channel();
+        let (worker_responses, responses) = channel();
+        let handle = std::thread::spawn(move || {
+            for request in worker_requests {
+                let response = match request {
+                    Request::Local(change) => match backend.apply_local_change(change) {
+                        Ok(applied) => Response::Local(applied),
+                        Err(e) => Response::Rejected(e, backend.get_patch()),
+                    },
+                    Request::Remote(changes) => Response::Remote(backend.apply_changes(changes)),
+                };
+                if worker_responses.send(response).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut document = Document::new(frontend);
+        document.set_track_pending(true);
+        Self {
+            document,
+            requests: Some(requests),
+            responses,
+            handle: Some(handle),
+            in_flight: 0,
+            outgoing: Vec::new(),
+        }
+    }
+
+    /// Get the current typed value, including any pending local changes.
+    pub fn get(&self) -> &T {
+        self.document.get()
+    }
+
+    /// The number of local changes the backend hasn't acknowledged yet.
+    pub fn pending_changes(&self) -> usize {
+        self.document.pending_changes().len()
+    }
+
+    /// Take the encoded local changes that have been acknowledged by the backend, ready to send
+    /// to other peers.
+    pub fn take_outgoing(&mut self) -> Vec<automerge::Change> {
+        std::mem::take(&mut self.outgoing)
+    }
+
+    /// Perform a change on the document, sending it to the backend.
+    pub fn change<C, O, E>(&mut self, change: C) -> Result<O, ThreadedDocumentError<E>>
+    where
+        T: Clone,
+        E: Error,
+        C: FnOnce(&mut T) -> Result<O, E>,
+    {
+        let (res, change) = self.document.change(change)?;
+        if let Some(change) = change {
+            self.send(Request::Local(change))?;
+        }
+        Ok(res)
+    }
+
+    /// Perform a change on the document with a message, sending it to the backend.
+    pub fn change_with_message<C, O, E>(
+        &mut self,
+        message: String,
+        change: C,
+    ) -> Result<O, ThreadedDocumentError<E>>
+    where
+        T: Clone,
+        E: Error,
+        C: FnOnce(&mut T) -> Result<O, E>,
+    {
+        let (res, change) = self.document.change_with_message(message, change)?;
+        if let Some(change) = change {
+            self.send(Request::Local(change))?;
+        }
+        Ok(res)
+    }
+
+    /// Calculate the changes that the closure would make, without applying them.
+    ///
+    /// See [`Document::preview_change`].
+    pub fn preview_change<C, O, E>(
+        &self,
+        change: C,
+    ) -> Result<(O, Vec<LocalChange>, DiffReport), ThreadedDocumentError<E>>
+    where
+        T: Clone,
+        E: Error,
+        C: FnOnce(&mut T) -> Result<O, E>,
+    {
+        Ok(self.document.preview_change(change)?)
+    }
+
+    /// Send changes from other peers to the backend.
+    pub fn apply_changes(
+        &mut self,
+        changes: Vec<automerge::Change>,
+    ) -> Result<(), ThreadedDocumentError> {
+        self.send(Request::Remote(changes))
+    }
+
+    /// Apply any patches the backend has finished, without blocking.
+    ///
+    /// Returns the number of patches applied.
+    pub fn process_patches(&mut self) -> Result<usize, ThreadedDocumentError> {
+        let mut processed = 0;
+        while self.in_flight > 0 {
+            match self.responses.try_recv() {
+                Ok(response) => {
+                    self.handle_response(response)?;
+                    processed += 1;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(ThreadedDocumentError::Disconnected),
+            }
+        }
+        Ok(processed)
+    }
+
+    /// Block until the backend has responded to everything sent to it, applying the patches.
+    pub fn flush(&mut self) -> Result<(), ThreadedDocumentError> {
+        while self.in_flight > 0 {
+            let response = self
+                .responses
+                .recv()
+                .map_err(|_| ThreadedDocumentError::Disconnected)?;
+            self.handle_response(response)?;
+        }
+        Ok(())
+    }
+
+    fn send<E: Error>(&mut self, request: Request) -> Result<(), ThreadedDocumentError<E>> {
+        self.requests
+            .as_ref()
+            .ok_or(ThreadedDocumentError::Disconnected)?
+            .send(request)
+            .map_err(|_| ThreadedDocumentError::Disconnected)?;
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    fn handle_response(&mut self, response: Response) -> Result<(), ThreadedDocumentError> {
+        self.in_flight -= 1;
+        let patch = match response {
+            Response::Local((patch, change)) => {
+                self.outgoing.push(change);
+                patch
+            }
+            Response::Rejected(e, patch) => {
+                self.document.reset(patch?)?;
+                return Err(e.into());
+            }
+            Response::Remote(result) => result?,
+        };
+        self.document.apply_patch(patch)?;
+        Ok(())
+    }
+}
+
+impl<T> Drop for ThreadedDocument<T>
+where
+    T: RootAutomergeable,
+{
+    fn drop(&mut self) {
+        // closing the channel stops the backend thread
+        self.requests.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn changes_sync_between_threaded_documents() {
+        let mut doc1 = ThreadedDocument::<HashMap<String, String>>::new(Frontend::new());
+        let mut doc2 = ThreadedDocument::<HashMap<String, String>>::new(Frontend::new());
+
+        doc1.change::<_, _, std::convert::Infallible>(|map| {
+            map.insert("a".to_owned(), "b".to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(doc1.pending_changes(), 1);
+        assert_eq!(doc1.get().get("a"), Some(&"b".to_owned()));
+
+        doc1.flush().unwrap();
+        assert_eq!(doc1.pending_changes(), 0);
+
+        let changes = doc1.take_outgoing();
+        assert_eq!(changes.len(), 1);
+        doc2.apply_changes(changes).unwrap();
+        doc2.flush().unwrap();
+
+        assert_eq!(doc1.get(), doc2.get());
+    }
+
+    #[test]
+    fn preview_and_message_changes() {
+        let mut doc = ThreadedDocument::<HashMap<String, String>>::new(Frontend::new());
+
+        let ((), changes, _) = doc
+            .preview_change::<_, _, std::convert::Infallible>(|map| {
+                map.insert("a".to_owned(), "b".to_owned());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(doc.get().is_empty());
+
+        doc.change_with_message::<_, _, std::convert::Infallible>("add a".to_owned(), |map| {
+            map.insert("a".to_owned(), "b".to_owned());
+            Ok(())
+        })
+        .unwrap();
+        doc.flush().unwrap();
+        let changes = doc.take_outgoing();
+        assert_eq!(changes[0].decode().message, Some("add a".to_owned()));
+    }
+}
@@ -1,7 +1,19 @@
-use std::{collections::HashMap, convert::Infallible};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use automerge::{InvalidChangeRequest, Path, Primitive, Value};
-use automergeable::diff_values;
+use automergeable::{
+    diff_values,
+    json::{from_json, to_json},
+    FromAutomerge, ToAutomerge, ToAutomergeError, TryToAutomerge,
+};
 use maplit::hashmap;
 use pretty_assertions::assert_eq;
 use quickcheck::{empty_shrinker, single_shrinker, Arbitrary, Gen, QuickCheck, TestResult};
@@ -266,6 +278,106 @@ fn equal_values_give_no_diff() {
         .quickcheck(no_diff as fn(Val) -> TestResult)
 }
 
+#[test]
+fn tagged_json_roundtrips() {
+    fn roundtrip(val: Val) -> TestResult {
+        match to_json(&val.0) {
+            Ok(json) => TestResult::from_bool(from_json(&json).as_ref() == Ok(&val.0)),
+            Err(_) => TestResult::discard(),
+        }
+    }
+    QuickCheck::new()
+        .gen(Gen::new(20))
+        .quickcheck(roundtrip as fn(Val) -> TestResult)
+}
+
+/// Check that a value converts to automerge and back unchanged.
+///
+/// Values that automerge documents it can't represent (`NaN`, paths that aren't unicode and
+/// numbers out of range) are discarded, any other conversion error fails the test.
+fn roundtrips<T>(value: T) -> TestResult
+where
+    T: TryToAutomerge + FromAutomerge + PartialEq + std::fmt::Debug,
+{
+    match value.try_to_automerge() {
+        Ok(automerge) => match T::from_automerge(&automerge) {
+            Ok(decoded) => TestResult::from_bool(decoded == value),
+            Err(e) => TestResult::error(format!("{:?} failed to decode: {}", value, e)),
+        },
+        Err(ToAutomergeError::NotANumber)
+        | Err(ToAutomergeError::NotUnicode(_))
+        | Err(ToAutomergeError::OutOfRange { .. }) => TestResult::discard(),
+        Err(e) => TestResult::error(format!("{:?} failed to convert: {}", value, e)),
+    }
+}
+
+#[test]
+fn std_types_roundtrip() {
+    fn floats(f: f32) -> TestResult {
+        roundtrips(f)
+    }
+    fn strings(s: String) -> TestResult {
+        roundtrips((
+            s.clone().into_boxed_str(),
+            Cow::<str>::Owned(s.clone()),
+            Cow::<str>::Borrowed(&s),
+        ))
+    }
+    fn tuples(t: (i64, String, bool), n: (u8, i16, char, Option<u64>)) -> TestResult {
+        roundtrips((t, n))
+    }
+    fn sequences(a: [i32; 4], v: VecDeque<u16>) -> TestResult {
+        roundtrips((a, v))
+    }
+    fn durations(d: Duration) -> TestResult {
+        // only milliseconds are kept
+        roundtrips(Duration::from_millis(d.as_millis() as u64))
+    }
+    fn paths(p: PathBuf) -> TestResult {
+        roundtrips(p)
+    }
+    fn addresses(ip: IpAddr, socket: SocketAddr) -> TestResult {
+        roundtrips((ip, socket))
+    }
+    fn orderings(a: i64, b: i64) -> TestResult {
+        roundtrips(a.cmp(&b))
+    }
+    fn results(r: Result<String, i64>) -> TestResult {
+        roundtrips(r)
+    }
+    let mut qc = QuickCheck::new();
+    qc.quickcheck(floats as fn(f32) -> TestResult);
+    qc.quickcheck(strings as fn(String) -> TestResult);
+    qc.quickcheck(tuples as fn((i64, String, bool), (u8, i16, char, Option<u64>)) -> TestResult);
+    qc.quickcheck(sequences as fn([i32; 4], VecDeque<u16>) -> TestResult);
+    qc.quickcheck(durations as fn(Duration) -> TestResult);
+    qc.quickcheck(paths as fn(PathBuf) -> TestResult);
+    qc.quickcheck(addresses as fn(IpAddr, SocketAddr) -> TestResult);
+    qc.quickcheck(orderings as fn(i64, i64) -> TestResult);
+    qc.quickcheck(results as fn(Result<String, i64>) -> TestResult);
+}
+
+#[test]
+fn std_types_representations() {
+    assert_eq!(
+        (1_i64, "a").to_automerge(),
+        Value::List(vec![
+            Value::Primitive(Primitive::Int(1)),
+            Value::Primitive(Primitive::Str("a".into()))
+        ])
+    );
+    assert_eq!(
+        Ordering::Less.to_automerge(),
+        Value::Primitive(Primitive::Str("Less".into()))
+    );
+    assert_eq!(
+        Ok::<_, ()>(true).to_automerge(),
+        Value::Map(hashmap! {"Ok".into() => Value::Primitive(Primitive::Boolean(true))})
+    );
+    assert!(<[u8; 2]>::from_automerge(&vec![1_u8].to_automerge()).is_err());
+    assert!(f32::from_automerge(&f64::MAX.to_automerge()).is_err());
+}
+
 #[test]
 fn applying_primitive_diff_result_to_old_gives_new() {
     fn apply_diff(p1: Prim, p2: Prim) -> TestResult {
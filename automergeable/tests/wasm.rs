@@ -8,9 +8,10 @@ fn make_change() {
         tasks: u8,
     }
 
-    let mut doc = automergeable::Document::<DocumentInner, _>::new(
-        automerge::Frontend::new_with_timestamper(Box::new(|| None)),
-    );
+    let (mut doc, _) = automergeable::DocumentBuilder::<DocumentInner>::new()
+        .without_timestamps()
+        .build()
+        .unwrap();
     let _change_result = doc
         .change::<_, _, automerge::InvalidChangeRequest>(|_d| Ok(()))
         .unwrap();
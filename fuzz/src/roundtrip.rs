@@ -1,7 +1,9 @@
 #![no_main]
 
+use std::collections::HashMap;
+
 use automerge::{Backend, InvalidChangeRequest, Primitive, Value};
-use automergeable::{unicode_segmentation::UnicodeSegmentation, DocumentChangeError};
+use automergeable::{unicode_segmentation::UnicodeSegmentation, DocumentChangeError, ToAutomerge};
 use libfuzzer_sys::fuzz_target;
 use pretty_assertions::assert_eq;
 
@@ -34,13 +36,19 @@ fuzz_target!(|values: Vec<automerge::Value>| {
         }
     }
 
-    let mut doc = automergeable::Document::<Value, _>::new(automerge::Frontend::new());
+    let mut doc =
+        automergeable::Document::<HashMap<String, Value>, _>::new(automerge::Frontend::new());
 
     let mut backend_bytes = Vec::new();
 
     for val in values {
         let change = doc.change::<_, _, InvalidChangeRequest>(|old| {
-            *old = val.clone();
+            if let Value::Map(map) = &val {
+                *old = map
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect();
+            }
             Ok(())
         });
 
@@ -53,11 +61,11 @@ fuzz_target!(|values: Vec<automerge::Value>| {
                         Backend::load(backend_bytes).unwrap()
                     };
                     let (patch, _) = backend.apply_local_change(c).unwrap();
-                    assert_eq!(doc.get(), &val);
+                    assert_eq!(doc.get().to_automerge(), val);
                     doc.apply_patch(patch).unwrap();
 
-                    let doc_val = doc.get();
-                    if doc_val != &val {
+                    let doc_val = doc.get().to_automerge();
+                    if doc_val != val {
                         println!(
                             "changes: {:?}",
                             backend
@@ -66,7 +74,7 @@ fuzz_target!(|values: Vec<automerge::Value>| {
                                 .map(|c| c.decode())
                                 .collect::<Vec<_>>()
                         );
-                        assert_eq!(doc_val, &val);
+                        assert_eq!(doc_val, val);
                     }
                     backend_bytes = backend.save().unwrap();
                 }